@@ -0,0 +1,47 @@
+/// Per-connection state that commands may read or change.
+#[derive(Debug)]
+pub struct Client {
+    /// Assigned at accept time and never reused while the server runs;
+    /// reported by CLIENT ID.
+    pub id: u64,
+    /// CLIENT NO-TOUCH: reads don't update a key's access time.
+    pub no_touch: bool,
+    /// CLIENT NO-EVICT: exempt from client eviction. Recorded only, since
+    /// clients are never evicted.
+    pub no_evict: bool,
+    /// RESP version the connection speaks. Always 2 for now, as there is no
+    /// HELLO to negotiate 3; replies that only exist in RESP3 check it and
+    /// fall back to their RESP2 form.
+    pub protocol: u8,
+    /// Whether commands other than AUTH are served. Cleared when the
+    /// connection is accepted while `requirepass` is set, until AUTH
+    /// succeeds.
+    pub authenticated: bool,
+    /// Bytes read from and written to this connection, reported by CLIENT
+    /// INFO as `tot-net-in` and `tot-net-out`.
+    pub net_input: u64,
+    pub net_output: u64,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            no_touch: false,
+            no_evict: false,
+            protocol: 2,
+            authenticated: true,
+            net_input: 0,
+            net_output: 0,
+        }
+    }
+}
+
+impl Client {
+    pub fn new(id: u64) -> Self {
+        Self {
+            id,
+            ..Self::default()
+        }
+    }
+}
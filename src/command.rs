@@ -1,95 +1,1206 @@
-use crate::resp::Frame;
-use crate::db::Db;
-use std::sync::Arc;
+use bytes::Bytes;
+use crate::client::Client;
+use crate::resp::{Frame, ReplyBuilder};
+use crate::db::{Db, IncrError, SetCondition};
+use crate::glob::glob_match;
+use crate::pause::{Pause, PauseMode};
+use crate::resp::parse_decimal;
+use std::time::{Duration, Instant};
+use thiserror::Error;
 
 #[derive(Debug)]
 pub enum Command {
+    Ping { message: Option<Bytes> },
     Get { key: String },
-    Set { key: String, value: Vec<u8> },
+    Set {
+        key: String,
+        value: Bytes,
+        condition: SetCondition,
+        /// Reply with the previous value instead of OK.
+        get: bool,
+    },
     Del { key: String },
+    GetDel { key: String },
+    Copy {
+        source: String,
+        destination: String,
+        replace: bool,
+    },
+    GetRange { key: String, start: i64, end: i64 },
+    IncrBy { key: String, delta: i64 },
+    Exists { keys: Vec<String> },
+    Info { sections: Vec<String> },
+    /// COMMAND GETKEYS: the full command line to extract keys from.
+    CommandGetKeys { args: Vec<Bytes> },
+    CommandCount,
+    /// There are no command docs, so this always replies with an empty
+    /// list; redis-cli then falls back to its built-in help.
+    CommandDocs,
+    ConfigGet { pattern: String },
+    ConfigSet { parameter: String, value: String },
+    ObjectIdletime { key: String },
+    ObjectFreq { key: String },
+    ObjectEncoding { key: String },
+    MemoryStats,
+    ClusterInfo,
+    ClusterMyId,
+    /// CLUSTER SLOTS and CLUSTER SHARDS, which are both empty without
+    /// cluster mode.
+    ClusterSlots,
+    ClusterShards,
+    ClientId,
+    ClientInfo,
+    ClientNoTouch { on: bool },
+    ClientNoEvict { on: bool },
+    ClientPause { timeout: Duration, mode: PauseMode },
+    ClientUnpause,
+    Dbsize,
+    /// WAIT: `timeout` of `None` waits forever. With no replicas nothing is
+    /// ever acknowledged, so process_client just sits out the timeout.
+    Wait {
+        numreplicas: i64,
+        timeout: Option<Duration>,
+    },
+    /// FLUSHDB and FLUSHALL, which are the same thing with a single database.
+    /// With `lazy` (ASYNC) the memory is reclaimed in the background.
+    FlushDb { lazy: bool },
+    FlushAll { lazy: bool },
+    Quit,
+    /// AUTH with the password alone or, as in Redis 6, with a username,
+    /// which can only be `default` as there are no ACL users.
+    Auth {
+        username: Option<String>,
+        password: String,
+    },
+    DebugStringmatchLen { pattern: Bytes, string: Bytes },
+    /// DEBUG PROTOCOL BIGNUM: a sample big number, for client testing.
+    DebugProtocolBignum,
+    /// `<command> HELP`: usage lines for a command's subcommands.
+    Help {
+        command: &'static str,
+        lines: &'static [&'static str],
+    },
+}
+
+/// An error reply. `Display` renders the exact Redis error text, prefix
+/// included, ready to be sent as a [`Frame::Error`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    /// Carries the (sub)command name, e.g. `"GET"` or `"config|get"`.
+    #[error("ERR wrong number of arguments for '{}' command", .0.to_lowercase())]
+    WrongArity(&'static str),
+    #[error("ERR value is not an integer or out of range")]
+    NotInteger,
+    #[error("ERR syntax error")]
+    Syntax,
+    #[error("ERR no such key")]
+    NoSuchKey,
+    #[error("ERR source and destination objects are the same")]
+    SameObject,
+    #[error("ERR Invalid command specified")]
+    GetKeysInvalidCommand,
+    #[error("ERR Invalid number of arguments specified for command")]
+    GetKeysWrongArity,
+    #[error("ERR The command has no key arguments")]
+    GetKeysNoKeys,
+    /// Carries the command name as the client sent it and the start of its
+    /// arguments, both already sanitized for the error line.
+    #[error("ERR unknown command '{name}', with args beginning with: {args}")]
+    Unknown { name: String, args: String },
+    #[error("ERR unknown subcommand '{subcommand}'. Try {command} HELP.")]
+    UnknownSubcommand {
+        command: &'static str,
+        subcommand: String,
+    },
+    #[error("ERR increment or decrement would overflow")]
+    Overflow,
+    #[error("ERR decrement would overflow")]
+    DecrementOverflow,
+    #[error("OOM command not allowed when used memory > 'maxmemory'.")]
+    Oom,
+    #[error("NOAUTH Authentication required.")]
+    NoAuth,
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+    #[error("ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?")]
+    AuthNotConfigured,
+    #[error("ERR This server does not support Lua scripting")]
+    NoScripting,
+    #[error("ERR An LFU maxmemory policy is not selected, access frequency not tracked. Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.")]
+    FreqNotTracked,
+    #[error("ERR An LFU maxmemory policy is selected, idle time not tracked. Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.")]
+    IdleTimeNotTracked,
+    /// A [`PROTECTED`] command while its config switch is off.
+    #[error("ERR {0} command not allowed")]
+    NotAllowed(&'static str),
+    /// A rejected CONFIG SET, with the message from [`Config::set`](crate::config::Config::set).
+    #[error("ERR {0}")]
+    Config(String),
+    /// A request that is not an array of bulk strings.
+    #[error("ERR {0}")]
+    Invalid(&'static str),
+}
+
+impl From<CommandError> for Frame {
+    fn from(e: CommandError) -> Frame {
+        Frame::Error(e.to_string())
+    }
+}
+
+/// Signature shared by every entry in the command table.
+type Parser = fn(&mut Args) -> Result<Command, CommandError>;
+
+/// The command may modify the keyspace.
+const WRITE: u8 = 1 << 0;
+/// The command may grow memory usage, so it is refused while over maxmemory.
+/// Health checks and administrative commands must never carry it: PING has
+/// to keep answering under memory pressure, and CONFIG SET is how an
+/// operator raises maxmemory to recover.
+const DENYOOM: u8 = 1 << 1;
+/// The command is refused unless enabled in the config (`enable-debug-command`).
+const PROTECTED: u8 = 1 << 2;
+/// Served before the connection has authenticated; everything else is
+/// refused with NOAUTH while `requirepass` is set.
+const NO_AUTH: u8 = 1 << 3;
+
+struct CommandSpec {
+    name: &'static str,
+    /// Redis-style arity, counting the command name itself: a positive value
+    /// is the exact number of arguments, a negative one the minimum.
+    arity: i32,
+    parse: Parser,
+    flags: u8,
+    keys: KeySpec,
+}
+
+/// Where a command's key arguments sit, in the form of Redis's legacy
+/// `COMMAND INFO` fields: positions `first..=last` every `step`, with a
+/// negative `last` counting back from the final argument. A `first` of 0
+/// means the command takes no keys.
+#[derive(Clone, Copy)]
+struct KeySpec {
+    first: usize,
+    last: i32,
+    step: usize,
+}
+
+const NO_KEYS: KeySpec = KeySpec { first: 0, last: 0, step: 0 };
+const ONE_KEY: KeySpec = KeySpec { first: 1, last: 1, step: 1 };
+const TWO_KEYS: KeySpec = KeySpec { first: 1, last: 2, step: 1 };
+const ALL_KEYS: KeySpec = KeySpec { first: 1, last: -1, step: 1 };
+
+/// Dispatch table mapping each (uppercase) command name to its parser.
+const COMMANDS: &[CommandSpec] = &[
+    CommandSpec { name: "PING", arity: -1, parse: parse_ping, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "GET", arity: 2, parse: parse_get, flags: 0, keys: ONE_KEY },
+    CommandSpec { name: "SET", arity: -3, parse: parse_set, flags: WRITE | DENYOOM, keys: ONE_KEY },
+    CommandSpec { name: "DEL", arity: 2, parse: parse_del, flags: WRITE, keys: ONE_KEY },
+    CommandSpec { name: "GETDEL", arity: 2, parse: parse_getdel, flags: WRITE, keys: ONE_KEY },
+    CommandSpec {
+        name: "COPY",
+        arity: -3,
+        parse: parse_copy,
+        flags: WRITE | DENYOOM,
+        keys: TWO_KEYS,
+    },
+    CommandSpec { name: "INCR", arity: 2, parse: parse_incr, flags: WRITE | DENYOOM, keys: ONE_KEY },
+    CommandSpec { name: "DECR", arity: 2, parse: parse_decr, flags: WRITE | DENYOOM, keys: ONE_KEY },
+    CommandSpec { name: "INCRBY", arity: 3, parse: parse_incrby, flags: WRITE | DENYOOM, keys: ONE_KEY },
+    CommandSpec { name: "DECRBY", arity: 3, parse: parse_decrby, flags: WRITE | DENYOOM, keys: ONE_KEY },
+    CommandSpec { name: "GETRANGE", arity: 4, parse: parse_getrange, flags: 0, keys: ONE_KEY },
+    // Legacy alias kept for older clients, sharing GETRANGE's handler.
+    CommandSpec { name: "SUBSTR", arity: 4, parse: parse_getrange, flags: 0, keys: ONE_KEY },
+    CommandSpec { name: "EXISTS", arity: -2, parse: parse_exists, flags: 0, keys: ALL_KEYS },
+    CommandSpec { name: "INFO", arity: -1, parse: parse_info, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "COMMAND", arity: -2, parse: parse_command, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "CONFIG", arity: -2, parse: parse_config, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "OBJECT", arity: -2, parse: parse_object, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "MEMORY", arity: -2, parse: parse_memory, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "CLUSTER", arity: -2, parse: parse_cluster, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "CLIENT", arity: -2, parse: parse_client, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "FLUSHDB", arity: -1, parse: parse_flushdb, flags: WRITE, keys: NO_KEYS },
+    CommandSpec { name: "FLUSHALL", arity: -1, parse: parse_flushall, flags: WRITE, keys: NO_KEYS },
+    CommandSpec { name: "WAIT", arity: 3, parse: parse_wait, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "DBSIZE", arity: 1, parse: parse_dbsize, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "QUIT", arity: -1, parse: parse_quit, flags: NO_AUTH, keys: NO_KEYS },
+    CommandSpec { name: "AUTH", arity: -2, parse: parse_auth, flags: NO_AUTH, keys: NO_KEYS },
+    CommandSpec { name: "EVAL", arity: -3, parse: parse_scripting, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "EVALSHA", arity: -3, parse: parse_scripting, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "EVAL_RO", arity: -3, parse: parse_scripting, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "EVALSHA_RO", arity: -3, parse: parse_scripting, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "SCRIPT", arity: -2, parse: parse_scripting, flags: 0, keys: NO_KEYS },
+    CommandSpec { name: "DEBUG", arity: -2, parse: parse_debug, flags: PROTECTED, keys: NO_KEYS },
+];
+
+/// Normalizes a command name or option keyword for matching.
+///
+/// Matching is always done on the uppercased token, while error messages
+/// should echo the token exactly as the client sent it.
+fn keyword(token: &[u8]) -> String {
+    String::from_utf8_lossy(token).to_uppercase()
+}
+
+/// Most characters of client input echoed back in an error, as in Redis.
+const EXCERPT_LEN: usize = 128;
+
+/// Renders client bytes for an error line: lossy UTF-8 with control
+/// characters blanked, since a stray CR or LF would end the reply early,
+/// and capped at `max` characters.
+fn excerpt(bytes: &[u8], max: usize) -> String {
+    String::from_utf8_lossy(bytes)
+        .chars()
+        .take(max)
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+/// Cursor over the arguments of a request, consumed by the command parsers.
+struct Args {
+    frames: std::vec::IntoIter<Frame>,
+    /// Total number of arguments in the request, including the command name.
+    argc: usize,
+}
+
+impl Args {
+    /// Checks `argc` against an arity spec, reporting a mismatch with the
+    /// canonical Redis message for the (sub)command `name`.
+    fn check_arity(&self, arity: i32, name: &'static str) -> Result<(), CommandError> {
+        let ok = if arity >= 0 {
+            self.argc == arity as usize
+        } else {
+            self.argc >= arity.unsigned_abs() as usize
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(CommandError::WrongArity(name))
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.frames.len()
+    }
+
+    fn next_bytes(&mut self) -> Option<Bytes> {
+        match self.frames.next() {
+            Some(Frame::Bulk(Some(bytes))) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    fn next_string(&mut self) -> Option<String> {
+        self.next_bytes()
+            .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    fn next_integer(&mut self) -> Result<i64, CommandError> {
+        self.next_bytes()
+            .and_then(|bytes| parse_decimal(&bytes))
+            .ok_or(CommandError::NotInteger)
+    }
+}
+
+fn parse_ping(args: &mut Args) -> Result<Command, CommandError> {
+    if args.remaining() > 1 {
+        return Err(CommandError::WrongArity("ping"));
+    }
+    Ok(Command::Ping {
+        message: args.next_bytes(),
+    })
+}
+
+fn parse_get(args: &mut Args) -> Result<Command, CommandError> {
+    let key = args.next_string().ok_or(CommandError::Invalid("GET expects key"))?;
+    Ok(Command::Get { key })
+}
+
+fn parse_set(args: &mut Args) -> Result<Command, CommandError> {
+    let key = args.next_string().ok_or(CommandError::Invalid("SET expects key"))?;
+    let value = args.next_bytes().ok_or(CommandError::Invalid("SET expects value"))?;
+    let mut condition = SetCondition::Always;
+    let mut get = false;
+    while args.remaining() > 0 {
+        let option = args.next_string().ok_or(CommandError::Syntax)?;
+        match keyword(option.as_bytes()).as_str() {
+            "NX" if condition != SetCondition::IfPresent => condition = SetCondition::IfAbsent,
+            "XX" if condition != SetCondition::IfAbsent => condition = SetCondition::IfPresent,
+            "GET" => get = true,
+            _ => return Err(CommandError::Syntax),
+        }
+    }
+    Ok(Command::Set { key, value, condition, get })
+}
+
+fn parse_del(args: &mut Args) -> Result<Command, CommandError> {
+    let key = args.next_string().ok_or(CommandError::Invalid("DEL expects key"))?;
+    Ok(Command::Del { key })
+}
+
+fn parse_getdel(args: &mut Args) -> Result<Command, CommandError> {
+    let key = args.next_string().ok_or(CommandError::Invalid("GETDEL expects key"))?;
+    Ok(Command::GetDel { key })
+}
+
+fn parse_copy(args: &mut Args) -> Result<Command, CommandError> {
+    let source = args.next_string().ok_or(CommandError::Invalid("COPY expects source"))?;
+    let destination = args
+        .next_string()
+        .ok_or(CommandError::Invalid("COPY expects destination"))?;
+    let mut replace = false;
+    while args.remaining() > 0 {
+        let option = args.next_string().ok_or(CommandError::Syntax)?;
+        match keyword(option.as_bytes()).as_str() {
+            "REPLACE" => replace = true,
+            // There is a single database, so DB is not accepted.
+            _ => return Err(CommandError::Syntax),
+        }
+    }
+    Ok(Command::Copy {
+        source,
+        destination,
+        replace,
+    })
+}
+
+fn parse_incr(args: &mut Args) -> Result<Command, CommandError> {
+    let key = args.next_string().ok_or(CommandError::Invalid("INCR expects key"))?;
+    Ok(Command::IncrBy { key, delta: 1 })
+}
+
+fn parse_decr(args: &mut Args) -> Result<Command, CommandError> {
+    let key = args.next_string().ok_or(CommandError::Invalid("DECR expects key"))?;
+    Ok(Command::IncrBy { key, delta: -1 })
+}
+
+fn parse_incrby(args: &mut Args) -> Result<Command, CommandError> {
+    let key = args.next_string().ok_or(CommandError::Invalid("INCRBY expects key"))?;
+    let delta = args.next_integer()?;
+    Ok(Command::IncrBy { key, delta })
+}
+
+fn parse_decrby(args: &mut Args) -> Result<Command, CommandError> {
+    let key = args.next_string().ok_or(CommandError::Invalid("DECRBY expects key"))?;
+    let delta = args
+        .next_integer()?
+        .checked_neg()
+        .ok_or(CommandError::DecrementOverflow)?;
+    Ok(Command::IncrBy { key, delta })
+}
+
+fn parse_getrange(args: &mut Args) -> Result<Command, CommandError> {
+    let key = args.next_string().ok_or(CommandError::Invalid("GETRANGE expects key"))?;
+    let start = args.next_integer()?;
+    let end = args.next_integer()?;
+    Ok(Command::GetRange { key, start, end })
+}
+
+fn parse_exists(args: &mut Args) -> Result<Command, CommandError> {
+    let mut keys = Vec::with_capacity(args.remaining());
+    while args.remaining() > 0 {
+        keys.push(args.next_string().ok_or(CommandError::Invalid("EXISTS expects key"))?);
+    }
+    Ok(Command::Exists { keys })
+}
+
+fn parse_info(args: &mut Args) -> Result<Command, CommandError> {
+    let mut sections = Vec::with_capacity(args.remaining());
+    while args.remaining() > 0 {
+        let section = args.next_string().ok_or(CommandError::Invalid("INFO expects section"))?;
+        sections.push(section.to_ascii_lowercase());
+    }
+    Ok(Command::Info { sections })
+}
+
+/// Parses `<command> HELP`, which takes no further arguments.
+fn parse_help(
+    args: &Args,
+    command: &'static str,
+    arity_name: &'static str,
+    lines: &'static [&'static str],
+) -> Result<Command, CommandError> {
+    args.check_arity(2, arity_name)?;
+    Ok(Command::Help { command, lines })
+}
+
+const COMMAND_HELP: &[&str] = &[
+    "COUNT",
+    "    Return the total number of commands in this Redis server.",
+    "DOCS [<command-name> ...]",
+    "    Return documentary information about commands.",
+    "GETKEYS <full-command>",
+    "    Return the keys from a full Redis command.",
+];
+
+fn parse_command(args: &mut Args) -> Result<Command, CommandError> {
+    let subcommand = args
+        .next_string()
+        .ok_or(CommandError::Invalid("COMMAND expects subcommand"))?;
+    match keyword(subcommand.as_bytes()).as_str() {
+        "GETKEYS" => {
+            args.check_arity(-3, "command|getkeys")?;
+            let mut line = Vec::with_capacity(args.remaining());
+            while args.remaining() > 0 {
+                line.push(args.next_bytes().ok_or(CommandError::Invalid("expected bulk string"))?);
+            }
+            Ok(Command::CommandGetKeys { args: line })
+        }
+        "COUNT" => {
+            args.check_arity(2, "command|count")?;
+            Ok(Command::CommandCount)
+        }
+        "DOCS" => Ok(Command::CommandDocs),
+        "HELP" => parse_help(args, "COMMAND", "command|help", COMMAND_HELP),
+        _ => Err(CommandError::UnknownSubcommand {
+            command: "COMMAND",
+            subcommand,
+        }),
+    }
+}
+
+/// Longest string Redis allocates together with its object header.
+const EMBSTR_SIZE_LIMIT: usize = 44;
+
+/// The encoding Redis would pick for a string value: `int` when it is the
+/// canonical decimal form of an i64, else `embstr` or `raw` by length.
+/// Values are plain bytes here either way, so this is derived on demand.
+fn string_encoding(value: &[u8]) -> &'static str {
+    let canonical_int = value.len() <= 20
+        && parse_decimal(value).is_some_and(|n| n.to_string().as_bytes() == value);
+    if canonical_int {
+        "int"
+    } else if value.len() <= EMBSTR_SIZE_LIMIT {
+        "embstr"
+    } else {
+        "raw"
+    }
+}
+
+/// Picks the key arguments out of a full command line using its table entry.
+fn get_keys(args: Vec<Bytes>) -> Result<Vec<Bytes>, CommandError> {
+    let name = keyword(&args[0]);
+    let spec = COMMANDS
+        .iter()
+        .find(|spec| spec.name == name)
+        .ok_or(CommandError::GetKeysInvalidCommand)?;
+    let argc = args.len();
+    let arity_ok = if spec.arity >= 0 {
+        argc == spec.arity as usize
+    } else {
+        argc >= spec.arity.unsigned_abs() as usize
+    };
+    if !arity_ok {
+        return Err(CommandError::GetKeysWrongArity);
+    }
+    let keys = spec.keys;
+    if keys.first == 0 {
+        return Err(CommandError::GetKeysNoKeys);
+    }
+    let last = if keys.last < 0 {
+        argc - keys.last.unsigned_abs() as usize
+    } else {
+        keys.last as usize
+    };
+    Ok(args
+        .into_iter()
+        .take(last + 1)
+        .skip(keys.first)
+        .step_by(keys.step)
+        .collect())
+}
+
+const CONFIG_HELP: &[&str] = &[
+    "GET <pattern>",
+    "    Return parameters matching the glob-like <pattern> and their values.",
+    "SET <directive> <value>",
+    "    Set the configuration <directive> to <value>.",
+];
+
+fn parse_config(args: &mut Args) -> Result<Command, CommandError> {
+    let subcommand = args.next_string().ok_or(CommandError::Invalid("CONFIG expects subcommand"))?;
+    match keyword(subcommand.as_bytes()).as_str() {
+        "GET" => {
+            args.check_arity(3, "config|get")?;
+            let pattern = args
+                .next_string()
+                .ok_or(CommandError::Invalid("CONFIG GET expects parameter"))?;
+            Ok(Command::ConfigGet { pattern })
+        }
+        "SET" => {
+            args.check_arity(4, "config|set")?;
+            let parameter = args
+                .next_string()
+                .ok_or(CommandError::Invalid("CONFIG SET expects parameter"))?;
+            let value = args
+                .next_string()
+                .ok_or(CommandError::Invalid("CONFIG SET expects value"))?;
+            Ok(Command::ConfigSet { parameter, value })
+        }
+        "HELP" => parse_help(args, "CONFIG", "config|help", CONFIG_HELP),
+        _ => Err(CommandError::UnknownSubcommand {
+            command: "CONFIG",
+            subcommand,
+        }),
+    }
+}
+
+const OBJECT_HELP: &[&str] = &[
+    "ENCODING <key>",
+    "    Return the kind of internal representation used in order to store the value",
+    "    associated with a <key>.",
+    "IDLETIME <key>",
+    "    Return the idle time of the key, that is the approximated number of",
+    "    seconds elapsed since the last access to the key.",
+    "FREQ <key>",
+    "    Return the access frequency index of the key. The returned integer is",
+    "    proportional to the logarithm of the recent access frequency of the key.",
+];
+
+fn parse_object(args: &mut Args) -> Result<Command, CommandError> {
+    let subcommand = args.next_string().ok_or(CommandError::Invalid("OBJECT expects subcommand"))?;
+    match keyword(subcommand.as_bytes()).as_str() {
+        "IDLETIME" => {
+            args.check_arity(3, "object|idletime")?;
+            let key = args
+                .next_string()
+                .ok_or(CommandError::Invalid("OBJECT IDLETIME expects key"))?;
+            Ok(Command::ObjectIdletime { key })
+        }
+        "FREQ" => {
+            args.check_arity(3, "object|freq")?;
+            let key = args
+                .next_string()
+                .ok_or(CommandError::Invalid("OBJECT FREQ expects key"))?;
+            Ok(Command::ObjectFreq { key })
+        }
+        "ENCODING" => {
+            args.check_arity(3, "object|encoding")?;
+            let key = args
+                .next_string()
+                .ok_or(CommandError::Invalid("OBJECT ENCODING expects key"))?;
+            Ok(Command::ObjectEncoding { key })
+        }
+        "HELP" => parse_help(args, "OBJECT", "object|help", OBJECT_HELP),
+        _ => Err(CommandError::UnknownSubcommand {
+            command: "OBJECT",
+            subcommand,
+        }),
+    }
+}
+
+const CLUSTER_HELP: &[&str] = &[
+    "INFO",
+    "    Return information about the cluster.",
+    "MYID",
+    "    Return the node id.",
+    "SHARDS",
+    "    Return information about slot range mappings and the nodes associated with them.",
+    "SLOTS",
+    "    Return information about slots range mappings. Each range is made of:",
+    "    start, end, master and replicas IP addresses, ports and ids",
+];
+
+/// Read-only answers for a standalone server, so cluster-aware clients
+/// that probe on connect see cluster mode off rather than an error.
+fn parse_cluster(args: &mut Args) -> Result<Command, CommandError> {
+    let subcommand = args
+        .next_string()
+        .ok_or(CommandError::Invalid("CLUSTER expects subcommand"))?;
+    let (command, arity_name) = match keyword(subcommand.as_bytes()).as_str() {
+        "INFO" => (Command::ClusterInfo, "cluster|info"),
+        "MYID" => (Command::ClusterMyId, "cluster|myid"),
+        "SLOTS" => (Command::ClusterSlots, "cluster|slots"),
+        "SHARDS" => (Command::ClusterShards, "cluster|shards"),
+        "HELP" => return parse_help(args, "CLUSTER", "cluster|help", CLUSTER_HELP),
+        _ => {
+            return Err(CommandError::UnknownSubcommand {
+                command: "CLUSTER",
+                subcommand,
+            })
+        }
+    };
+    args.check_arity(2, arity_name)?;
+    Ok(command)
+}
+
+const MEMORY_HELP: &[&str] = &[
+    "STATS",
+    "    Return information about the memory usage of the server.",
+];
+
+fn parse_memory(args: &mut Args) -> Result<Command, CommandError> {
+    let subcommand = args.next_string().ok_or(CommandError::Invalid("MEMORY expects subcommand"))?;
+    match keyword(subcommand.as_bytes()).as_str() {
+        "STATS" => {
+            args.check_arity(2, "memory|stats")?;
+            Ok(Command::MemoryStats)
+        }
+        "HELP" => parse_help(args, "MEMORY", "memory|help", MEMORY_HELP),
+        _ => Err(CommandError::UnknownSubcommand {
+            command: "MEMORY",
+            subcommand,
+        }),
+    }
+}
+
+const CLIENT_HELP: &[&str] = &[
+    "ID",
+    "    Return the ID of the current connection.",
+    "INFO",
+    "    Return information about the current client connection.",
+    "NO-EVICT (ON|OFF)",
+    "    Protect the current client connection from eviction.",
+    "NO-TOUCH (ON|OFF)",
+    "    Will not touch LRU/LFU stats when this mode is on.",
+    "PAUSE <timeout> [WRITE|ALL]",
+    "    Suspend all, or just write, clients for <timeout> milliseconds.",
+    "UNPAUSE",
+    "    Stop the current client pause, resuming traffic.",
+];
+
+/// Parses the ON|OFF argument of a CLIENT mode switch.
+fn parse_on_off(args: &mut Args) -> Result<bool, CommandError> {
+    match args.next_bytes().map(|token| keyword(&token)).as_deref() {
+        Some("ON") => Ok(true),
+        Some("OFF") => Ok(false),
+        _ => Err(CommandError::Syntax),
+    }
+}
+
+fn parse_client(args: &mut Args) -> Result<Command, CommandError> {
+    let subcommand = args.next_string().ok_or(CommandError::Invalid("CLIENT expects subcommand"))?;
+    match keyword(subcommand.as_bytes()).as_str() {
+        "ID" => {
+            args.check_arity(2, "client|id")?;
+            Ok(Command::ClientId)
+        }
+        "INFO" => {
+            args.check_arity(2, "client|info")?;
+            Ok(Command::ClientInfo)
+        }
+        "NO-TOUCH" => {
+            args.check_arity(3, "client|no-touch")?;
+            Ok(Command::ClientNoTouch { on: parse_on_off(args)? })
+        }
+        "NO-EVICT" => {
+            args.check_arity(3, "client|no-evict")?;
+            Ok(Command::ClientNoEvict { on: parse_on_off(args)? })
+        }
+        "PAUSE" => {
+            args.check_arity(-3, "client|pause")?;
+            let timeout = next_timeout(args)?;
+            let mode = match args.next_bytes().map(|token| keyword(&token)).as_deref() {
+                None | Some("ALL") => PauseMode::All,
+                Some("WRITE") => PauseMode::Write,
+                Some(_) => return Err(CommandError::Syntax),
+            };
+            if args.remaining() > 0 {
+                return Err(CommandError::Syntax);
+            }
+            Ok(Command::ClientPause { timeout, mode })
+        }
+        "UNPAUSE" => {
+            args.check_arity(2, "client|unpause")?;
+            Ok(Command::ClientUnpause)
+        }
+        "HELP" => parse_help(args, "CLIENT", "client|help", CLIENT_HELP),
+        _ => Err(CommandError::UnknownSubcommand {
+            command: "CLIENT",
+            subcommand,
+        }),
+    }
+}
+
+fn parse_wait(args: &mut Args) -> Result<Command, CommandError> {
+    let numreplicas = args.next_integer()?;
+    let timeout = next_timeout(args)?;
+    Ok(Command::Wait {
+        numreplicas,
+        timeout: (!timeout.is_zero()).then_some(timeout),
+    })
+}
+
+/// Parses a non-negative timeout in milliseconds, with Redis's messages.
+fn next_timeout(args: &mut Args) -> Result<Duration, CommandError> {
+    let millis = args
+        .next_integer()
+        .map_err(|_| CommandError::Invalid("timeout is not an integer or out of range"))?;
+    let millis = u64::try_from(millis).map_err(|_| CommandError::Invalid("timeout is negative"))?;
+    Ok(Duration::from_millis(millis))
+}
+
+fn parse_dbsize(_args: &mut Args) -> Result<Command, CommandError> {
+    Ok(Command::Dbsize)
+}
+
+fn parse_flush_mode(args: &mut Args) -> Result<bool, CommandError> {
+    let lazy = match args.next_string() {
+        None => false,
+        Some(mode) => match keyword(mode.as_bytes()).as_str() {
+            "ASYNC" => true,
+            "SYNC" => false,
+            _ => return Err(CommandError::Syntax),
+        },
+    };
+    if args.remaining() > 0 {
+        return Err(CommandError::Syntax);
+    }
+    Ok(lazy)
+}
+
+fn parse_flushdb(args: &mut Args) -> Result<Command, CommandError> {
+    Ok(Command::FlushDb {
+        lazy: parse_flush_mode(args)?,
+    })
+}
+
+fn parse_flushall(args: &mut Args) -> Result<Command, CommandError> {
+    Ok(Command::FlushAll {
+        lazy: parse_flush_mode(args)?,
+    })
+}
+
+/// Scripting is registered only to be refused by name, so clients get a
+/// clear answer (and can feature-detect) rather than an unknown command.
+fn parse_scripting(_args: &mut Args) -> Result<Command, CommandError> {
+    Err(CommandError::NoScripting)
+}
+
+fn parse_quit(_args: &mut Args) -> Result<Command, CommandError> {
+    Ok(Command::Quit)
+}
+
+fn parse_auth(args: &mut Args) -> Result<Command, CommandError> {
+    let (username, password) = match args.remaining() {
+        1 => (None, args.next_string()),
+        2 => (args.next_string(), args.next_string()),
+        _ => return Err(CommandError::Syntax),
+    };
+    let password = password.ok_or(CommandError::Invalid("AUTH expects password"))?;
+    Ok(Command::Auth { username, password })
+}
+
+const DEBUG_HELP: &[&str] = &[
+    "STRINGMATCH-LEN <pattern> <string>",
+    "    Run a fuzz tester against the stringmatchlen() function.",
+    "PROTOCOL <type>",
+    "    Reply with a test value of the specified type. <type> can be: bignum.",
+];
+
+fn parse_debug(args: &mut Args) -> Result<Command, CommandError> {
+    let subcommand = args.next_string().ok_or(CommandError::Invalid("DEBUG expects subcommand"))?;
+    match keyword(subcommand.as_bytes()).as_str() {
+        "STRINGMATCH-LEN" => {
+            args.check_arity(4, "debug|stringmatch-len")?;
+            let pattern = args
+                .next_bytes()
+                .ok_or(CommandError::Invalid("DEBUG STRINGMATCH-LEN expects pattern"))?;
+            let string = args
+                .next_bytes()
+                .ok_or(CommandError::Invalid("DEBUG STRINGMATCH-LEN expects string"))?;
+            Ok(Command::DebugStringmatchLen { pattern, string })
+        }
+        "PROTOCOL" => {
+            args.check_arity(3, "debug|protocol")?;
+            match args.next_bytes().map(|token| keyword(&token)).as_deref() {
+                Some("BIGNUM") => Ok(Command::DebugProtocolBignum),
+                _ => Err(CommandError::Invalid(
+                    "Wrong protocol type name. Please use one of the following: bignum",
+                )),
+            }
+        }
+        "HELP" => parse_help(args, "DEBUG", "debug|help", DEBUG_HELP),
+        _ => Err(CommandError::UnknownSubcommand {
+            command: "DEBUG",
+            subcommand,
+        }),
+    }
 }
 
 impl Command {
-    pub fn from_frame(frame: Frame) -> Result<Command, String> {
-        match frame {
-            Frame::Array(Some(array)) => {
-                let mut array = array.into_iter();
-                
-                let command = match array.next() {
-                    Some(Frame::Bulk(Some(bytes))) => {
-                        String::from_utf8_lossy(&bytes).to_uppercase()
-                    }
-                    _ => return Err("expected bulk string".to_string()),
-                };
+    /// Parses a request.
+    pub fn from_frame(frame: Frame) -> Result<Command, CommandError> {
+        let mut args = match frame {
+            Frame::Array(Some(array)) => Args {
+                argc: array.len(),
+                frames: array.into_iter(),
+            },
+            _ => return Err(CommandError::Invalid("expected array")),
+        };
 
-                match command.as_str() {
-                    "GET" => {
-                        let key = match array.next() {
-                            Some(Frame::Bulk(Some(bytes))) => {
-                                String::from_utf8_lossy(&bytes).to_string()
-                            }
-                            _ => return Err("GET expects key".to_string()),
-                        };
-                        Ok(Command::Get { key })
-                    }
-                    "SET" => {
-                        let key = match array.next() {
-                            Some(Frame::Bulk(Some(bytes))) => {
-                                String::from_utf8_lossy(&bytes).to_string()
-                            }
-                            _ => return Err("SET expects key".to_string()),
-                        };
-                        let value = match array.next() {
-                            Some(Frame::Bulk(Some(bytes))) => bytes,
-                            _ => return Err("SET expects value".to_string()),
-                        };
-                        Ok(Command::Set { key, value })
-                    }
-                    "DEL" => {
-                        let key = match array.next() {
-                            Some(Frame::Bulk(Some(bytes))) => {
-                                String::from_utf8_lossy(&bytes).to_string()
-                            }
-                            _ => return Err("DEL expects key".to_string()),
-                        };
-                        Ok(Command::Del { key })
-                    }
-                    _ => Err(format!("unknown command '{}'", command)),
+        let name = args.next_bytes().ok_or(CommandError::Invalid("expected bulk string"))?;
+        let command = keyword(&name);
+
+        match COMMANDS.iter().find(|spec| spec.name == command) {
+            Some(spec) => {
+                args.check_arity(spec.arity, spec.name)?;
+                (spec.parse)(&mut args)
+            }
+            None => {
+                let mut quoted = String::new();
+                while quoted.len() < EXCERPT_LEN {
+                    let Some(arg) = args.next_bytes() else {
+                        break;
+                    };
+                    let arg = excerpt(&arg, EXCERPT_LEN - quoted.len());
+                    quoted.push_str(&format!("'{arg}' "));
                 }
+                Err(CommandError::Unknown {
+                    name: excerpt(&name, EXCERPT_LEN),
+                    args: quoted,
+                })
             }
-            _ => Err("expected array".to_string()),
         }
     }
 
-    pub fn execute(self, db: &Arc<Db>) -> Frame {
+    /// Deadline CLIENT PAUSE holds this command back until, if any. CLIENT
+    /// itself is never held, so a pause can always be lifted early.
+    pub fn paused_until(&self, pause: &Pause) -> Option<Instant> {
+        let name = self.name();
+        if name == "CLIENT" {
+            return None;
+        }
+        let write = COMMANDS
+            .iter()
+            .any(|spec| spec.name == name && spec.flags & WRITE != 0);
+        pause.deadline(write)
+    }
+
+    /// Name of the command's entry in the dispatch table.
+    pub fn name(&self) -> &'static str {
         match self {
+            Command::Ping { .. } => "PING",
+            Command::Get { .. } => "GET",
+            Command::Set { .. } => "SET",
+            Command::Del { .. } => "DEL",
+            Command::GetDel { .. } => "GETDEL",
+            Command::Copy { .. } => "COPY",
+            Command::CommandGetKeys { .. } | Command::CommandCount | Command::CommandDocs => {
+                "COMMAND"
+            }
+            Command::GetRange { .. } => "GETRANGE",
+            Command::IncrBy { .. } => "INCRBY",
+            Command::Exists { .. } => "EXISTS",
+            Command::Info { .. } => "INFO",
+            Command::ConfigGet { .. } | Command::ConfigSet { .. } => "CONFIG",
+            Command::ObjectIdletime { .. }
+            | Command::ObjectFreq { .. }
+            | Command::ObjectEncoding { .. } => "OBJECT",
+            Command::MemoryStats => "MEMORY",
+            Command::ClusterInfo
+            | Command::ClusterMyId
+            | Command::ClusterSlots
+            | Command::ClusterShards => "CLUSTER",
+            Command::ClientId
+            | Command::ClientInfo
+            | Command::ClientNoTouch { .. }
+            | Command::ClientNoEvict { .. }
+            | Command::ClientPause { .. }
+            | Command::ClientUnpause => "CLIENT",
+            Command::Dbsize => "DBSIZE",
+            Command::Wait { .. } => "WAIT",
+            Command::FlushDb { .. } => "FLUSHDB",
+            Command::FlushAll { .. } => "FLUSHALL",
+            Command::Quit => "QUIT",
+            Command::Auth { .. } => "AUTH",
+            Command::DebugStringmatchLen { .. } | Command::DebugProtocolBignum => "DEBUG",
+            Command::Help { command, .. } => command,
+        }
+    }
+
+    fn flags(&self) -> u8 {
+        COMMANDS
+            .iter()
+            .find(|spec| spec.name == self.name())
+            .map_or(0, |spec| spec.flags)
+    }
+
+    /// Runs the command against `db` on behalf of the connection `client`.
+    pub fn execute(self, db: &Db, client: &mut Client) -> Result<Frame, CommandError> {
+        let flags = self.flags();
+        if flags & NO_AUTH == 0 && !client.authenticated {
+            return Err(CommandError::NoAuth);
+        }
+        if flags & PROTECTED != 0 && !db.config().enable_debug_command {
+            return Err(CommandError::NotAllowed(self.name()));
+        }
+        if flags & DENYOOM != 0 && db.enforce_maxmemory() {
+            return Err(CommandError::Oom);
+        }
+
+        let frame = match self {
+            Command::Ping { message: None } => Frame::Simple("PONG".to_string()),
+            Command::Ping { message } => Frame::Bulk(message),
             Command::Get { key } => {
-                match db.get(&key) {
+                match db.get_with(&key, !client.no_touch) {
                     Some(value) => Frame::Bulk(Some(value)),
                     None => Frame::Bulk(None),
                 }
             }
-            Command::Set { key, value } => {
-                db.set(key, value);
-                Frame::Simple("OK".to_string())
+            Command::Set { key, value, condition, get } => {
+                let (written, old) = db.set_with(key, value, condition);
+                if get {
+                    Frame::Bulk(old)
+                } else if written {
+                    Frame::Simple("OK".to_string())
+                } else {
+                    Frame::Bulk(None)
+                }
             }
             Command::Del { key } => {
                 let deleted = db.delete(&key);
                 Frame::Integer(if deleted { 1 } else { 0 })
             }
-        }
+            Command::GetDel { key } => Frame::Bulk(db.remove(&key)),
+            Command::Copy {
+                source,
+                destination,
+                replace,
+            } => {
+                if source == destination {
+                    return Err(CommandError::SameObject);
+                }
+                let copied = match db.get_with(&source, !client.no_touch) {
+                    Some(value) => {
+                        let condition = if replace {
+                            SetCondition::Always
+                        } else {
+                            SetCondition::IfAbsent
+                        };
+                        db.set_with(destination, value, condition).0
+                    }
+                    None => false,
+                };
+                Frame::Integer(copied as i64)
+            }
+            Command::CommandCount => Frame::Integer(COMMANDS.len() as i64),
+            Command::CommandDocs => Frame::Array(Some(Vec::new())),
+            Command::CommandGetKeys { args } => Frame::Array(Some(
+                get_keys(args)?
+                    .into_iter()
+                    .map(|key| Frame::Bulk(Some(key)))
+                    .collect(),
+            )),
+            Command::GetRange { key, start, end } => {
+                let value = db.get_with(&key, !client.no_touch).unwrap_or_default();
+                Frame::Bulk(Some(value.slice_ref(byte_range(&value, start, end))))
+            }
+            Command::IncrBy { key, delta } => match db.incr_by(&key, delta) {
+                Ok(value) => Frame::Integer(value),
+                Err(IncrError::NotInteger) => return Err(CommandError::NotInteger),
+                Err(IncrError::Overflow) => return Err(CommandError::Overflow),
+            },
+            Command::Exists { keys } => {
+                // Repeated keys are counted once per mention, as in Redis.
+                let count = keys.iter().filter(|key| db.exists(key)).count();
+                Frame::Integer(count as i64)
+            }
+            Command::ConfigGet { pattern } => {
+                let params = db.config().get(&pattern);
+                let mut reply = ReplyBuilder::with_capacity(params.len() * 2);
+                for (name, value) in params {
+                    reply = reply.field(name, Frame::Bulk(Some(Bytes::from(value))));
+                }
+                reply.build()
+            }
+            Command::ConfigSet { parameter, value } => {
+                match db.set_config(&parameter, &value) {
+                    Ok(()) => Frame::Simple("OK".to_string()),
+                    Err(e) => return Err(CommandError::Config(e)),
+                }
+            }
+            Command::ObjectIdletime { key } => {
+                if db.config().maxmemory_policy.is_lfu() {
+                    return Err(CommandError::IdleTimeNotTracked);
+                }
+                match db.idle_time(&key) {
+                    Some(idle) => Frame::Integer(idle.as_secs() as i64),
+                    None => return Err(CommandError::NoSuchKey),
+                }
+            }
+            Command::ObjectEncoding { key } => match db.get_with(&key, false) {
+                Some(value) => {
+                    Frame::Bulk(Some(Bytes::from_static(string_encoding(&value).as_bytes())))
+                }
+                None => return Err(CommandError::NoSuchKey),
+            },
+            Command::ObjectFreq { key } => {
+                if !db.config().maxmemory_policy.is_lfu() {
+                    return Err(CommandError::FreqNotTracked);
+                }
+                match db.freq(&key) {
+                    Some(freq) => Frame::Integer(i64::from(freq)),
+                    None => return Err(CommandError::NoSuchKey),
+                }
+            }
+            Command::Info { sections } => Frame::Bulk(Some(Bytes::from(info(db, &sections)))),
+            Command::MemoryStats => {
+                let keys = db.dbsize();
+                let dataset = db.used_memory();
+                let fields = [
+                    ("peak.allocated", db.peak_memory()),
+                    ("total.allocated", dataset),
+                    ("keys.count", keys),
+                    ("keys.bytes-per-key", dataset.checked_div(keys).unwrap_or(0)),
+                    ("dataset.bytes", dataset),
+                ];
+                let mut reply = ReplyBuilder::with_capacity(fields.len() * 2);
+                for (name, value) in fields {
+                    reply = reply.field(name, Frame::Integer(value as i64));
+                }
+                reply.build()
+            }
+            Command::ClusterInfo => Frame::Bulk(Some(Bytes::from_static(
+                b"cluster_enabled:0\r\ncluster_state:ok\r\ncluster_slots_assigned:0\r\n\
+                  cluster_known_nodes:1\r\ncluster_size:0\r\n",
+            ))),
+            Command::ClusterMyId => {
+                Frame::Bulk(Some(Bytes::copy_from_slice(db.node_id().as_bytes())))
+            }
+            Command::ClusterSlots | Command::ClusterShards => Frame::Array(Some(Vec::new())),
+            Command::Dbsize => Frame::Integer(db.dbsize() as i64),
+            // Replicas that acknowledged: there are none.
+            Command::Wait { .. } => Frame::Integer(0),
+            Command::FlushDb { lazy } | Command::FlushAll { lazy } => {
+                db.flush(lazy);
+                Frame::Simple("OK".to_string())
+            }
+            Command::ClientId => Frame::Integer(client.id as i64),
+            // Only the fields this server tracks, in Redis's order.
+            Command::ClientInfo => Frame::Bulk(Some(Bytes::from(format!(
+                "id={} resp={} tot-net-in={} tot-net-out={}\n",
+                client.id, client.protocol, client.net_input, client.net_output
+            )))),
+            Command::ClientNoTouch { on } => {
+                client.no_touch = on;
+                Frame::Simple("OK".to_string())
+            }
+            Command::ClientNoEvict { on } => {
+                client.no_evict = on;
+                Frame::Simple("OK".to_string())
+            }
+            Command::ClientPause { timeout, mode } => {
+                db.pause().pause(Instant::now() + timeout, mode);
+                Frame::Simple("OK".to_string())
+            }
+            Command::ClientUnpause => {
+                db.pause().unpause();
+                Frame::Simple("OK".to_string())
+            }
+            // The connection is closed by process_client once this is sent.
+            Command::Quit => Frame::Simple("OK".to_string()),
+            Command::Auth { username, password } => {
+                let Some(required) = db.config().requirepass.clone() else {
+                    return Err(CommandError::AuthNotConfigured);
+                };
+                if username.is_some_and(|name| name != "default") || password != required {
+                    return Err(CommandError::WrongPass);
+                }
+                client.authenticated = true;
+                Frame::Simple("OK".to_string())
+            }
+            Command::DebugProtocolBignum => {
+                // Same value Redis uses; beyond i64, so RESP2 gets the digits.
+                let number = "1234567999999999999999999999999999999".to_string();
+                if client.protocol >= 3 {
+                    Frame::BigNumber(number)
+                } else {
+                    Frame::Bulk(Some(Bytes::from(number)))
+                }
+            }
+            Command::DebugStringmatchLen { pattern, string } => {
+                Frame::Integer(glob_match(&pattern, &string, false) as i64)
+            }
+            Command::Help { command, lines } => {
+                let header = format!(
+                    "{} <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                    command
+                );
+                ReplyBuilder::with_capacity(lines.len() + 3)
+                    .simple(header)
+                    .extend(lines.iter().map(|line| Frame::Simple(line.to_string())))
+                    .simple("HELP")
+                    .simple("    Print this help.")
+                    .build()
+            }
+        };
+        Ok(frame)
     }
 }
 
+/// Slices `value` by the inclusive, possibly negative offsets GETRANGE takes,
+/// clamping out-of-range offsets like Redis does.
+fn byte_range(value: &[u8], start: i64, end: i64) -> &[u8] {
+    let len = value.len() as i64;
+    let start = if start < 0 { (len + start).max(0) } else { start };
+    let end = if end < 0 { (len + end).max(0) } else { end.min(len - 1) };
+    if len == 0 || start > end {
+        return &[];
+    }
+    &value[start as usize..=end as usize]
+}
+
+/// Renders the INFO reply. With no section, or `all`/`default`/`everything`,
+/// every section is included; unknown sections are silently skipped.
+fn info(db: &Db, sections: &[String]) -> String {
+    let wants = |name: &str| {
+        sections.is_empty()
+            || sections
+                .iter()
+                .any(|s| s == name || s == "all" || s == "default" || s == "everything")
+    };
+
+    let mut out = String::new();
+    if wants("memory") {
+        let config = db.config();
+        out.push_str("# Memory\r\n");
+        out.push_str(&format!("used_memory:{}\r\n", db.used_memory()));
+        out.push_str(&format!("maxmemory:{}\r\n", config.maxmemory));
+        out.push_str(&format!("maxmemory_policy:{}\r\n", config.maxmemory_policy.as_str()));
+        out.push_str("\r\n");
+    }
+    if wants("cluster") {
+        out.push_str("# Cluster\r\n");
+        out.push_str("cluster_enabled:0\r\n");
+        out.push_str("\r\n");
+    }
+    if wants("stats") {
+        let stats = db.stats();
+        out.push_str("# Stats\r\n");
+        out.push_str(&format!("total_commands_processed:{}\r\n", stats.commands_processed()));
+        out.push_str(&format!("total_net_input_bytes:{}\r\n", stats.net_input_bytes()));
+        out.push_str(&format!("total_net_output_bytes:{}\r\n", stats.net_output_bytes()));
+        out.push_str(&format!("evicted_keys:{}\r\n", stats.evicted_keys()));
+        out.push_str("\r\n");
+    }
+    // Like Redis, no trailing blank line after the last section.
+    out.truncate(out.trim_end().len());
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{Config, MaxMemoryPolicy};
+
+    /// Parses `args` as a request and runs it, with errors as their reply.
+    fn run(db: &Db, client: &mut Client, args: &[&str]) -> Frame {
+        let frame = Frame::Array(Some(
+            args.iter()
+                .map(|arg| Frame::Bulk(Some(Bytes::copy_from_slice(arg.as_bytes()))))
+                .collect(),
+        ));
+        Command::from_frame(frame)
+            .and_then(|cmd| cmd.execute(db, client))
+            .unwrap_or_else(Frame::from)
+    }
 
     #[test]
     fn test_parse_get() {
         let frame = Frame::Array(Some(vec![
-            Frame::Bulk(Some(b"GET".to_vec())),
-            Frame::Bulk(Some(b"key1".to_vec())),
+            Frame::Bulk(Some(Bytes::from_static(b"GET"))),
+            Frame::Bulk(Some(Bytes::from_static(b"key1"))),
         ]));
         
         match Command::from_frame(frame).unwrap() {
@@ -101,15 +1212,15 @@ mod tests {
     #[test]
     fn test_parse_set() {
         let frame = Frame::Array(Some(vec![
-            Frame::Bulk(Some(b"SET".to_vec())),
-            Frame::Bulk(Some(b"key1".to_vec())),
-            Frame::Bulk(Some(b"value1".to_vec())),
+            Frame::Bulk(Some(Bytes::from_static(b"SET"))),
+            Frame::Bulk(Some(Bytes::from_static(b"key1"))),
+            Frame::Bulk(Some(Bytes::from_static(b"value1"))),
         ]));
         
         match Command::from_frame(frame).unwrap() {
-            Command::Set { key, value } => {
+            Command::Set { key, value, .. } => {
                 assert_eq!(key, "key1");
-                assert_eq!(value, b"value1");
+                assert_eq!(value, &b"value1"[..]);
             }
             _ => panic!("expected SET command"),
         }
@@ -117,28 +1228,922 @@ mod tests {
 
     #[test]
     fn test_execute_commands() {
-        let db = Arc::new(Db::new());
+        let db = Db::new();
         
         // Test SET
         let cmd = Command::Set {
             key: "key1".to_string(),
-            value: b"value1".to_vec(),
+            value: Bytes::from_static(b"value1"),
+            condition: SetCondition::Always,
+            get: false,
         };
-        let result = cmd.execute(&db);
+        let result = cmd.execute(&db, &mut Client::default()).unwrap();
         assert_eq!(result, Frame::Simple("OK".to_string()));
         
         // Test GET
         let cmd = Command::Get {
             key: "key1".to_string(),
         };
-        let result = cmd.execute(&db);
-        assert_eq!(result, Frame::Bulk(Some(b"value1".to_vec())));
+        let result = cmd.execute(&db, &mut Client::default()).unwrap();
+        assert_eq!(result, Frame::Bulk(Some(Bytes::from_static(b"value1"))));
         
         // Test DEL
         let cmd = Command::Del {
             key: "key1".to_string(),
         };
-        let result = cmd.execute(&db);
+        let result = cmd.execute(&db, &mut Client::default()).unwrap();
         assert_eq!(result, Frame::Integer(1));
     }
+
+    #[test]
+    fn test_parse_mixed_case_command() {
+        let frame = Frame::Array(Some(vec![
+            Frame::Bulk(Some(Bytes::from_static(b"gEt"))),
+            Frame::Bulk(Some(Bytes::from_static(b"Key1"))),
+        ]));
+
+        match Command::from_frame(frame).unwrap() {
+            Command::Get { key } => assert_eq!(key, "Key1"),
+            _ => panic!("expected GET command"),
+        }
+    }
+
+    #[test]
+    fn test_unknown_command_keeps_original_case() {
+        let frame = Frame::Array(Some(vec![
+            Frame::Bulk(Some(Bytes::from_static(b"GeTx"))),
+            Frame::Bulk(Some(Bytes::from_static(b"key1"))),
+        ]));
+
+        let err = Command::from_frame(frame).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ERR unknown command 'GeTx', with args beginning with: 'key1' "
+        );
+    }
+
+    #[test]
+    fn test_unknown_command_name_is_sanitized() {
+        let frame = Frame::Array(Some(vec![
+            Frame::Bulk(Some(Bytes::new())),
+            Frame::Bulk(Some(Bytes::from_static(b"a"))),
+            Frame::Bulk(Some(Bytes::from_static(b"b"))),
+        ]));
+        assert_eq!(
+            Command::from_frame(frame).unwrap_err().to_string(),
+            "ERR unknown command '', with args beginning with: 'a' 'b' "
+        );
+
+        let frame = Frame::Array(Some(vec![
+            Frame::Bulk(Some(Bytes::from_static(b"x\r\n\0\xff"))),
+            Frame::Bulk(Some(Bytes::from_static(b"-ERR\r\n"))),
+        ]));
+        assert_eq!(
+            Command::from_frame(frame).unwrap_err().to_string(),
+            "ERR unknown command 'x   \u{FFFD}', with args beginning with: '-ERR  ' "
+        );
+
+        let long = Bytes::from(vec![b'k'; 300]);
+        let frame = Frame::Array(Some(vec![
+            Frame::Bulk(Some(long.clone())),
+            Frame::Bulk(Some(long)),
+            Frame::Bulk(Some(Bytes::from_static(b"unseen"))),
+        ]));
+        let Err(CommandError::Unknown { name, args }) = Command::from_frame(frame) else {
+            panic!("expected an unknown command error");
+        };
+        assert_eq!(name.len(), EXCERPT_LEN);
+        assert_eq!(args, format!("'{}' ", "k".repeat(EXCERPT_LEN)));
+    }
+
+    #[test]
+    fn test_oom_rejects_only_denyoom_commands() {
+        let db = Db::with_config(Config {
+            maxmemory: 1,
+            maxmemory_policy: MaxMemoryPolicy::NoEviction,
+            ..Config::default()
+        });
+        db.set("key1".to_string(), b"value1".to_vec());
+
+        let cmd = Command::Set {
+            key: "key2".to_string(),
+            value: Bytes::from_static(b"value2"),
+            condition: SetCondition::Always,
+            get: false,
+        };
+        assert_eq!(cmd.execute(&db, &mut Client::default()), Err(CommandError::Oom));
+        assert_eq!(db.get("key2"), None);
+
+        let cmd = Command::Get {
+            key: "key1".to_string(),
+        };
+        assert_eq!(
+            cmd.execute(&db, &mut Client::default()).unwrap(),
+            Frame::Bulk(Some(Bytes::from_static(b"value1")))
+        );
+
+        let cmd = Command::Del {
+            key: "key1".to_string(),
+        };
+        assert_eq!(cmd.execute(&db, &mut Client::default()).unwrap(), Frame::Integer(1));
+    }
+
+    #[test]
+    fn test_health_and_admin_commands_allowed_under_oom() {
+        let db = Db::with_config(Config {
+            maxmemory: 1,
+            maxmemory_policy: MaxMemoryPolicy::NoEviction,
+            ..Config::default()
+        });
+        db.set("key1".to_string(), b"value1".to_vec());
+        assert!(db.is_oom());
+
+        for name in ["PING", "CONFIG", "QUIT"] {
+            let spec = COMMANDS.iter().find(|spec| spec.name == name).unwrap();
+            assert_eq!(spec.flags & DENYOOM, 0, "{} must not be DENYOOM", name);
+        }
+
+        let cmd = Command::Ping { message: None };
+        assert_eq!(
+            cmd.execute(&db, &mut Client::default()).unwrap(),
+            Frame::Simple("PONG".to_string())
+        );
+
+        let cmd = Command::ConfigGet {
+            pattern: "maxmemory".to_string(),
+        };
+        assert!(matches!(cmd.execute(&db, &mut Client::default()).unwrap(), Frame::Array(Some(_))));
+
+        let cmd = Command::ConfigSet {
+            parameter: "maxmemory".to_string(),
+            value: "0".to_string(),
+        };
+        assert_eq!(
+            cmd.execute(&db, &mut Client::default()).unwrap(),
+            Frame::Simple("OK".to_string())
+        );
+        assert!(!db.is_oom());
+    }
+
+    #[test]
+    fn test_config_get_glob() {
+        let db = Db::new();
+
+        let cmd = Command::ConfigSet {
+            parameter: "maxmemory".to_string(),
+            value: "1mb".to_string(),
+        };
+        assert_eq!(
+            cmd.execute(&db, &mut Client::default()).unwrap(),
+            Frame::Simple("OK".to_string())
+        );
+
+        let cmd = Command::ConfigGet {
+            pattern: "maxmemory*".to_string(),
+        };
+        assert_eq!(
+            cmd.execute(&db, &mut Client::default()).unwrap(),
+            Frame::Array(Some(vec![
+                Frame::Bulk(Some(Bytes::from_static(b"maxmemory"))),
+                Frame::Bulk(Some(Bytes::from_static(b"1048576"))),
+                Frame::Bulk(Some(Bytes::from_static(b"maxmemory-policy"))),
+                Frame::Bulk(Some(Bytes::from_static(b"noeviction"))),
+                Frame::Bulk(Some(Bytes::from_static(b"maxmemory-samples"))),
+                Frame::Bulk(Some(Bytes::from_static(b"5"))),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_object_idletime() {
+        let db = Db::new();
+        let idletime = || {
+            Command::ObjectIdletime {
+                key: "key1".to_string(),
+            }
+            .execute(&db, &mut Client::default())
+        };
+
+        assert_eq!(idletime(), Err(CommandError::NoSuchKey));
+
+        db.set("key1".to_string(), b"value1".to_vec());
+        assert_eq!(idletime(), Ok(Frame::Integer(0)));
+
+        db.backdate("key1", std::time::Duration::from_secs(1));
+        assert_eq!(idletime(), Ok(Frame::Integer(1)));
+
+        db.get("key1");
+        assert_eq!(idletime(), Ok(Frame::Integer(0)));
+    }
+
+    #[test]
+    fn test_wrong_arity() {
+        let cases: &[(&[&str], &str)] = &[
+            (&["GET"], "get"),
+            (&["get", "key1", "extra"], "get"),
+            (&["SET", "key1"], "set"),
+            (&["Set"], "set"),
+            (&["DEL"], "del"),
+            (&["DEL", "key1", "extra"], "del"),
+            (&["CONFIG"], "config"),
+            (&["CONFIG", "GET"], "config|get"),
+        ];
+
+        for (args, name) in cases {
+            let frame = Frame::Array(Some(
+                args.iter()
+                    .map(|arg| Frame::Bulk(Some(Bytes::copy_from_slice(arg.as_bytes()))))
+                    .collect(),
+            ));
+            assert_eq!(
+                Command::from_frame(frame).unwrap_err().to_string(),
+                format!("ERR wrong number of arguments for '{}' command", name),
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_value_is_not_nil() {
+        let db = Db::new();
+
+        let cmd = Command::Set {
+            key: "empty".to_string(),
+            value: Bytes::new(),
+            condition: SetCondition::Always,
+            get: false,
+        };
+        assert_eq!(
+            cmd.execute(&db, &mut Client::default()).unwrap(),
+            Frame::Simple("OK".to_string())
+        );
+
+        let cmd = Command::Get {
+            key: "empty".to_string(),
+        };
+        assert_eq!(
+            cmd.execute(&db, &mut Client::default()).unwrap(),
+            Frame::Bulk(Some(Bytes::new()))
+        );
+
+        let cmd = Command::Get {
+            key: "missing".to_string(),
+        };
+        assert_eq!(cmd.execute(&db, &mut Client::default()).unwrap(), Frame::Bulk(None));
+
+        let cmd = Command::Exists {
+            keys: vec!["empty".to_string(), "missing".to_string()],
+        };
+        assert_eq!(cmd.execute(&db, &mut Client::default()).unwrap(), Frame::Integer(1));
+    }
+
+    #[test]
+    fn test_substr_matches_getrange() {
+        let db = Db::new();
+        db.set("key1".to_string(), b"Hello World".to_vec());
+
+        let ranges = [(0, 4), (-5, -1), (3, 100), (5, 2), (-100, 1)];
+        for (start, end) in ranges {
+            let reply = |name: &str| {
+                let frame = Frame::Array(Some(vec![
+                    Frame::Bulk(Some(Bytes::copy_from_slice(name.as_bytes()))),
+                    Frame::Bulk(Some(Bytes::from_static(b"key1"))),
+                    Frame::Bulk(Some(Bytes::from(start.to_string()))),
+                    Frame::Bulk(Some(Bytes::from(end.to_string()))),
+                ]));
+                Command::from_frame(frame).unwrap().execute(&db, &mut Client::default()).unwrap()
+            };
+            assert_eq!(reply("SUBSTR"), reply("GETRANGE"), "{} {}", start, end);
+        }
+
+        let cmd = Command::GetRange {
+            key: "key1".to_string(),
+            start: -5,
+            end: -1,
+        };
+        assert_eq!(
+            cmd.execute(&db, &mut Client::default()).unwrap(),
+            Frame::Bulk(Some(Bytes::from_static(b"World")))
+        );
+
+        let cmd = Command::GetRange {
+            key: "missing".to_string(),
+            start: 0,
+            end: -1,
+        };
+        assert_eq!(
+            cmd.execute(&db, &mut Client::default()).unwrap(),
+            Frame::Bulk(Some(Bytes::new()))
+        );
+    }
+
+    #[test]
+    fn test_debug_stringmatch_len() {
+        let db = Db::with_config(Config {
+            enable_debug_command: true,
+            ..Config::default()
+        });
+        let cases: &[(&[u8], &[u8], i64)] = &[
+            (b"h[a-z]llo", b"hello", 1),
+            (b"h[a-z]llo", b"hEllo", 0),
+            (b"h\\*llo", b"h*llo", 1),
+            (b"h\\*llo", b"hello", 0),
+            (b"h*llo", b"heeeello", 1),
+            (b"h?llo", b"hllo", 0),
+            // From the Redis suite; this used to take exponential time.
+            (
+                b"a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*b",
+                b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+                0,
+            ),
+        ];
+
+        for (pattern, string, expected) in cases {
+            let frame = Frame::Array(Some(vec![
+                Frame::Bulk(Some(Bytes::from_static(b"DEBUG"))),
+                Frame::Bulk(Some(Bytes::from_static(b"stringmatch-len"))),
+                Frame::Bulk(Some(Bytes::copy_from_slice(pattern))),
+                Frame::Bulk(Some(Bytes::copy_from_slice(string))),
+            ]));
+            let reply = Command::from_frame(frame)
+                .unwrap()
+                .execute(&db, &mut Client::default())
+                .unwrap();
+            assert_eq!(reply, Frame::Integer(*expected), "{:?}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_ping() {
+        let db = Db::new();
+        let ping = |args: &[&[u8]]| {
+            let mut frames = vec![Frame::Bulk(Some(Bytes::from_static(b"PING")))];
+            frames.extend(args.iter().map(|arg| Frame::Bulk(Some(Bytes::copy_from_slice(arg)))));
+            Command::from_frame(Frame::Array(Some(frames)))
+                .and_then(|cmd| cmd.execute(&db, &mut Client::default()))
+        };
+
+        assert_eq!(ping(&[]), Ok(Frame::Simple("PONG".to_string())));
+        assert_eq!(ping(&[b"hello"]), Ok(Frame::Bulk(Some(Bytes::from_static(b"hello")))));
+        assert_eq!(
+            ping(&[b"a", b"b"]),
+            Err(CommandError::WrongArity("ping"))
+        );
+    }
+
+    #[test]
+    fn test_incr_overflow() {
+        let db = Db::new();
+        let mut client = Client::default();
+        let overflow = Frame::Error("ERR increment or decrement would overflow".to_string());
+
+        db.set("max".to_string(), b"9223372036854775807".to_vec());
+        assert_eq!(run(&db, &mut client, &["INCR", "max"]), overflow);
+        assert_eq!(db.get("max"), Some(Bytes::from_static(b"9223372036854775807")));
+
+        db.set("min".to_string(), b"-9223372036854775808".to_vec());
+        assert_eq!(run(&db, &mut client, &["DECR", "min"]), overflow);
+        assert_eq!(run(&db, &mut client, &["INCRBY", "min", "-1"]), overflow);
+        assert_eq!(db.get("min"), Some(Bytes::from_static(b"-9223372036854775808")));
+
+        assert_eq!(
+            run(&db, &mut client, &["DECRBY", "zero", "-9223372036854775808"]),
+            Frame::Error("ERR decrement would overflow".to_string())
+        );
+        assert_eq!(db.get("zero"), None);
+
+        assert_eq!(run(&db, &mut client, &["INCRBY", "n", "10"]), Frame::Integer(10));
+        assert_eq!(run(&db, &mut client, &["DECRBY", "n", "3"]), Frame::Integer(7));
+        assert_eq!(
+            run(&db, &mut client, &["INCRBY", "n", "1.5"]),
+            Frame::Error("ERR value is not an integer or out of range".to_string())
+        );
+        assert_eq!(
+            run(&db, &mut client, &["INCRBY", "n", "007"]),
+            Frame::Error("ERR value is not an integer or out of range".to_string())
+        );
+    }
+
+    #[test]
+    fn test_memory_stats() {
+        let db = Db::new();
+        db.set("a".to_string(), b"1".to_vec());
+        db.set("b".to_string(), b"22".to_vec());
+
+        let reply = match Command::MemoryStats.execute(&db, &mut Client::default()).unwrap() {
+            Frame::Array(Some(items)) => items,
+            other => panic!("expected array, got {:?}", other),
+        };
+        let field = |name: &str| {
+            let i = reply
+                .iter()
+                .position(|f| *f == Frame::Bulk(Some(Bytes::copy_from_slice(name.as_bytes()))))
+                .unwrap();
+            reply[i + 1].clone()
+        };
+        assert_eq!(
+            field("keys.count"),
+            Command::Dbsize.execute(&db, &mut Client::default()).unwrap()
+        );
+        assert_eq!(field("keys.count"), Frame::Integer(2));
+        assert_eq!(field("dataset.bytes"), Frame::Integer(5));
+        assert_eq!(field("peak.allocated"), Frame::Integer(5));
+    }
+
+    #[test]
+    fn test_set_nx_xx_get() {
+        let db = Db::new();
+        let mut client = Client::default();
+        let ok = Frame::Simple("OK".to_string());
+        let bulk = |v: &str| Frame::Bulk(Some(Bytes::copy_from_slice(v.as_bytes())));
+
+        // NX
+        assert_eq!(run(&db, &mut client, &["SET", "k", "v1", "NX"]), ok);
+        assert_eq!(run(&db, &mut client, &["SET", "k", "v2", "nx"]), Frame::Bulk(None));
+        assert_eq!(db.get("k"), Some(Bytes::from_static(b"v1")));
+
+        // NX GET: returns the old value whether or not it writes.
+        assert_eq!(run(&db, &mut client, &["SET", "k", "v2", "NX", "GET"]), bulk("v1"));
+        assert_eq!(db.get("k"), Some(Bytes::from_static(b"v1")));
+        assert_eq!(run(&db, &mut client, &["SET", "fresh", "v", "GET", "NX"]), Frame::Bulk(None));
+        assert_eq!(db.get("fresh"), Some(Bytes::from_static(b"v")));
+
+        // XX
+        assert_eq!(run(&db, &mut client, &["SET", "missing", "v", "XX"]), Frame::Bulk(None));
+        assert_eq!(run(&db, &mut client, &["SET", "k", "v3", "XX"]), ok);
+        assert_eq!(db.get("k"), Some(Bytes::from_static(b"v3")));
+
+        // XX GET
+        assert_eq!(run(&db, &mut client, &["SET", "missing", "v", "XX", "GET"]), Frame::Bulk(None));
+        assert_eq!(db.get("missing"), None);
+        assert_eq!(run(&db, &mut client, &["SET", "k", "v4", "XX", "GET"]), bulk("v3"));
+        assert_eq!(db.get("k"), Some(Bytes::from_static(b"v4")));
+
+        // GET alone
+        assert_eq!(run(&db, &mut client, &["SET", "k", "v5", "GET"]), bulk("v4"));
+        assert_eq!(db.get("k"), Some(Bytes::from_static(b"v5")));
+        assert_eq!(run(&db, &mut client, &["SET", "new", "v", "GET"]), Frame::Bulk(None));
+        assert_eq!(db.get("new"), Some(Bytes::from_static(b"v")));
+        db.delete("new");
+        assert_eq!(db.used_memory(), "k".len() + 2 + "fresh".len() + 1);
+
+        let syntax = Frame::Error("ERR syntax error".to_string());
+        assert_eq!(run(&db, &mut client, &["SET", "k", "v", "NX", "XX"]), syntax);
+        assert_eq!(run(&db, &mut client, &["SET", "k", "v", "BOGUS"]), syntax);
+    }
+
+    #[test]
+    fn test_command_error_wire_text() {
+        let cases = [
+            (
+                CommandError::WrongType,
+                "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n",
+            ),
+            (
+                CommandError::WrongArity("GET"),
+                "-ERR wrong number of arguments for 'get' command\r\n",
+            ),
+            (
+                CommandError::WrongArity("config|get"),
+                "-ERR wrong number of arguments for 'config|get' command\r\n",
+            ),
+            (
+                CommandError::NotInteger,
+                "-ERR value is not an integer or out of range\r\n",
+            ),
+            (CommandError::Syntax, "-ERR syntax error\r\n"),
+            (CommandError::NoSuchKey, "-ERR no such key\r\n"),
+            (
+                CommandError::Unknown {
+                    name: "FooBar".to_string(),
+                    args: "'x' ".to_string(),
+                },
+                "-ERR unknown command 'FooBar', with args beginning with: 'x' \r\n",
+            ),
+            (
+                CommandError::UnknownSubcommand {
+                    command: "OBJECT",
+                    subcommand: "nope".to_string(),
+                },
+                "-ERR unknown subcommand 'nope'. Try OBJECT HELP.\r\n",
+            ),
+            (
+                CommandError::Overflow,
+                "-ERR increment or decrement would overflow\r\n",
+            ),
+            (
+                CommandError::Oom,
+                "-OOM command not allowed when used memory > 'maxmemory'.\r\n",
+            ),
+        ];
+
+        for (error, wire) in cases {
+            assert_eq!(Frame::from(error).encode(), wire.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_help_subcommands() {
+        let db = Db::with_config(Config {
+            enable_debug_command: true,
+            ..Config::default()
+        });
+        let mut client = Client::default();
+
+        for command in ["OBJECT", "config", "MEMORY", "DEBUG", "Client", "command"] {
+            match run(&db, &mut client, &[command, "help"]) {
+                Frame::Array(Some(lines)) => {
+                    assert!(lines.len() > 3, "{}", command);
+                    assert!(lines.iter().all(|line| matches!(line, Frame::Simple(_))));
+                }
+                other => panic!("{} HELP returned {:?}", command, other),
+            }
+        }
+
+        assert_eq!(
+            run(&db, &mut client, &["OBJECT", "HELP", "extra"]),
+            Frame::from(CommandError::WrongArity("object|help"))
+        );
+        assert_eq!(
+            run(&db, &mut client, &["object", "nope"]),
+            Frame::Error("ERR unknown subcommand 'nope'. Try OBJECT HELP.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_debug_requires_enable_debug_command() {
+        let debug = || Command::DebugStringmatchLen {
+            pattern: Bytes::from_static(b"a*"),
+            string: Bytes::from_static(b"abc"),
+        };
+
+        let db = Db::new();
+        assert_eq!(
+            debug().execute(&db, &mut Client::default()),
+            Err(CommandError::NotAllowed("DEBUG"))
+        );
+        assert_eq!(
+            Frame::from(CommandError::NotAllowed("DEBUG")),
+            Frame::Error("ERR DEBUG command not allowed".to_string())
+        );
+
+        let db = Db::with_config(Config {
+            enable_debug_command: true,
+            ..Config::default()
+        });
+        assert_eq!(debug().execute(&db, &mut Client::default()), Ok(Frame::Integer(1)));
+    }
+
+    #[test]
+    fn test_client_id() {
+        let db = Db::new();
+        let mut client = Client::new(42);
+        assert_eq!(Command::ClientId.execute(&db, &mut client), Ok(Frame::Integer(42)));
+    }
+
+    #[test]
+    fn test_client_no_touch() {
+        let db = Db::new();
+        let mut client = Client::new(1);
+
+        assert_eq!(run(&db, &mut client, &["SET", "key1", "v"]), Frame::Simple("OK".to_string()));
+        assert_eq!(
+            run(&db, &mut client, &["CLIENT", "NO-TOUCH", "on"]),
+            Frame::Simple("OK".to_string())
+        );
+        assert_eq!(
+            run(&db, &mut client, &["CLIENT", "NO-EVICT", "ON"]),
+            Frame::Simple("OK".to_string())
+        );
+        assert_eq!(
+            run(&db, &mut client, &["CLIENT", "NO-TOUCH", "maybe"]),
+            Frame::from(CommandError::Syntax)
+        );
+
+        db.backdate("key1", std::time::Duration::from_secs(1));
+        assert_eq!(
+            run(&db, &mut client, &["GET", "key1"]),
+            Frame::Bulk(Some(Bytes::from_static(b"v")))
+        );
+        assert_eq!(
+            run(&db, &mut client, &["GETRANGE", "key1", "0", "-1"]),
+            Frame::Bulk(Some(Bytes::from_static(b"v")))
+        );
+        assert_eq!(run(&db, &mut client, &["OBJECT", "IDLETIME", "key1"]), Frame::Integer(1));
+
+        assert_eq!(
+            run(&db, &mut client, &["CLIENT", "NO-TOUCH", "off"]),
+            Frame::Simple("OK".to_string())
+        );
+        run(&db, &mut client, &["GET", "key1"]);
+        assert_eq!(run(&db, &mut client, &["OBJECT", "IDLETIME", "key1"]), Frame::Integer(0));
+        assert!(!client.no_touch);
+        assert!(client.no_evict);
+    }
+
+    #[test]
+    fn test_object_freq() {
+        let db = Db::new();
+        let mut client = Client::default();
+        let freq = |db: &Db, client: &mut Client| {
+            Command::ObjectFreq {
+                key: "key1".to_string(),
+            }
+            .execute(db, client)
+        };
+        db.set("key1".to_string(), b"v".to_vec());
+        assert_eq!(freq(&db, &mut client), Err(CommandError::FreqNotTracked));
+
+        db.set_config("maxmemory-policy", "allkeys-lfu").unwrap();
+        assert_eq!(freq(&db, &mut client), Ok(Frame::Integer(5)));
+        for _ in 0..100 {
+            db.get("key1");
+        }
+        match freq(&db, &mut client) {
+            Ok(Frame::Integer(n)) => assert!(n > 5, "{}", n),
+            other => panic!("expected an integer, got {:?}", other),
+        }
+
+        let idletime = Command::ObjectIdletime {
+            key: "key1".to_string(),
+        };
+        assert_eq!(
+            idletime.execute(&db, &mut client),
+            Err(CommandError::IdleTimeNotTracked)
+        );
+
+        let missing = Command::ObjectFreq {
+            key: "missing".to_string(),
+        };
+        assert_eq!(missing.execute(&db, &mut client), Err(CommandError::NoSuchKey));
+    }
+
+    #[test]
+    fn test_command_getkeys() {
+        let db = Db::new();
+        let mut client = Client::default();
+        let keys = |keys: &[&str]| {
+            Frame::Array(Some(
+                keys.iter()
+                    .map(|key| Frame::Bulk(Some(Bytes::copy_from_slice(key.as_bytes()))))
+                    .collect(),
+            ))
+        };
+
+        assert_eq!(
+            run(&db, &mut client, &["COMMAND", "GETKEYS", "copy", "src", "dst"]),
+            keys(&["src", "dst"])
+        );
+        assert_eq!(
+            run(&db, &mut client, &["COMMAND", "GETKEYS", "COPY", "src", "dst", "REPLACE"]),
+            keys(&["src", "dst"])
+        );
+        assert_eq!(run(&db, &mut client, &["COMMAND", "GETKEYS", "GETDEL", "k"]), keys(&["k"]));
+        assert_eq!(
+            run(&db, &mut client, &["COMMAND", "GETKEYS", "SET", "k", "v", "NX"]),
+            keys(&["k"])
+        );
+        assert_eq!(
+            run(&db, &mut client, &["COMMAND", "GETKEYS", "EXISTS", "a", "b", "c"]),
+            keys(&["a", "b", "c"])
+        );
+        assert_eq!(
+            run(&db, &mut client, &["COMMAND", "GETKEYS", "PING"]),
+            Frame::from(CommandError::GetKeysNoKeys)
+        );
+        assert_eq!(
+            run(&db, &mut client, &["COMMAND", "GETKEYS", "GET"]),
+            Frame::from(CommandError::GetKeysWrongArity)
+        );
+        assert_eq!(
+            run(&db, &mut client, &["COMMAND", "GETKEYS", "NOPE", "k"]),
+            Frame::from(CommandError::GetKeysInvalidCommand)
+        );
+    }
+
+    #[test]
+    fn test_getdel_and_copy() {
+        let db = Db::new();
+        let mut client = Client::default();
+
+        db.set("src".to_string(), b"v1".to_vec());
+        assert_eq!(run(&db, &mut client, &["COPY", "src", "dst"]), Frame::Integer(1));
+        assert_eq!(db.get("dst"), Some(Bytes::from_static(b"v1")));
+
+        db.set("src".to_string(), b"v2".to_vec());
+        assert_eq!(run(&db, &mut client, &["COPY", "src", "dst"]), Frame::Integer(0));
+        assert_eq!(db.get("dst"), Some(Bytes::from_static(b"v1")));
+        assert_eq!(run(&db, &mut client, &["COPY", "src", "dst", "replace"]), Frame::Integer(1));
+        assert_eq!(db.get("dst"), Some(Bytes::from_static(b"v2")));
+        assert_eq!(run(&db, &mut client, &["COPY", "missing", "dst"]), Frame::Integer(0));
+        assert_eq!(
+            run(&db, &mut client, &["COPY", "src", "src"]),
+            Frame::from(CommandError::SameObject)
+        );
+        assert_eq!(
+            run(&db, &mut client, &["COPY", "src", "dst", "DB", "1"]),
+            Frame::from(CommandError::Syntax)
+        );
+
+        assert_eq!(
+            run(&db, &mut client, &["GETDEL", "dst"]),
+            Frame::Bulk(Some(Bytes::from_static(b"v2")))
+        );
+        assert_eq!(run(&db, &mut client, &["GETDEL", "dst"]), Frame::Bulk(None));
+        assert_eq!(db.used_memory(), "src".len() + 2);
+    }
+
+    #[test]
+    fn test_flush_async() {
+        let db = Db::new();
+        let mut client = Client::default();
+        let ok = Frame::Simple("OK".to_string());
+
+        for i in 0..100_000 {
+            db.set(format!("key{i}"), vec![b'x'; 1024]);
+        }
+        let start = std::time::Instant::now();
+        assert_eq!(run(&db, &mut client, &["FLUSHALL", "async"]), ok);
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+        assert_eq!(db.dbsize(), 0);
+        assert_eq!(run(&db, &mut client, &["GET", "key0"]), Frame::Bulk(None));
+        // The memory is given back once the background thread frees it.
+        while db.used_memory() > 0 {
+            assert!(start.elapsed() < std::time::Duration::from_secs(5));
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        db.set("k".to_string(), b"v".to_vec());
+        assert_eq!(run(&db, &mut client, &["FLUSHDB", "SYNC"]), ok);
+        assert_eq!(db.dbsize(), 0);
+        assert_eq!(run(&db, &mut client, &["FLUSHDB"]), ok);
+        assert_eq!(run(&db, &mut client, &["FLUSHDB", "LATER"]), Frame::from(CommandError::Syntax));
+        assert_eq!(
+            run(&db, &mut client, &["FLUSHALL", "ASYNC", "SYNC"]),
+            Frame::from(CommandError::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_incr_get_round_trip() {
+        let db = Db::new();
+        let mut client = Client::default();
+        let bulk = |v: &str| Frame::Bulk(Some(Bytes::copy_from_slice(v.as_bytes())));
+
+        for _ in 0..3 {
+            run(&db, &mut client, &["INCR", "n"]);
+        }
+        assert_eq!(run(&db, &mut client, &["GET", "n"]), bulk("3"));
+
+        assert_eq!(run(&db, &mut client, &["SET", "n", "-41"]), Frame::Simple("OK".to_string()));
+        assert_eq!(run(&db, &mut client, &["INCRBY", "n", "100"]), Frame::Integer(59));
+        assert_eq!(run(&db, &mut client, &["GET", "n"]), bulk("59"));
+        assert_eq!(run(&db, &mut client, &["DECRBY", "n", "59"]), Frame::Integer(0));
+        assert_eq!(run(&db, &mut client, &["GET", "n"]), bulk("0"));
+        assert_eq!(run(&db, &mut client, &["GETRANGE", "n", "0", "-1"]), bulk("0"));
+        assert_eq!(db.used_memory(), "n".len() + 1);
+    }
+
+    #[test]
+    fn test_debug_protocol_bignum() {
+        let db = Db::with_config(Config {
+            enable_debug_command: true,
+            ..Config::default()
+        });
+        let number = "1234567999999999999999999999999999999";
+
+        let mut resp2 = Client::default();
+        assert_eq!(
+            Command::DebugProtocolBignum.execute(&db, &mut resp2),
+            Ok(Frame::Bulk(Some(Bytes::copy_from_slice(number.as_bytes()))))
+        );
+        let mut resp3 = Client {
+            protocol: 3,
+            ..Client::default()
+        };
+        assert_eq!(
+            Command::DebugProtocolBignum.execute(&db, &mut resp3),
+            Ok(Frame::BigNumber(number.to_string()))
+        );
+
+        let frame = Frame::Array(Some(vec![
+            Frame::Bulk(Some(Bytes::from_static(b"DEBUG"))),
+            Frame::Bulk(Some(Bytes::from_static(b"PROTOCOL"))),
+            Frame::Bulk(Some(Bytes::from_static(b"double"))),
+        ]));
+        assert!(matches!(Command::from_frame(frame), Err(CommandError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_object_encoding() {
+        let db = Db::new();
+        let encoding = |value: &[u8]| {
+            db.set("k".to_string(), value.to_vec());
+            Command::ObjectEncoding {
+                key: "k".to_string(),
+            }
+            .execute(&db, &mut Client::default())
+        };
+        let bulk = |v: &str| Ok(Frame::Bulk(Some(Bytes::copy_from_slice(v.as_bytes()))));
+
+        assert_eq!(encoding(b"123"), bulk("int"));
+        assert_eq!(encoding(b"-9223372036854775808"), bulk("int"));
+        assert_eq!(encoding(b"0123"), bulk("embstr"));
+        assert_eq!(encoding(b"+1"), bulk("embstr"));
+        assert_eq!(encoding(b"9223372036854775808"), bulk("embstr"));
+        assert_eq!(encoding(b"helloworld"), bulk("embstr"));
+        assert_eq!(encoding(&[b'x'; EMBSTR_SIZE_LIMIT]), bulk("embstr"));
+        assert_eq!(encoding(&[b'x'; EMBSTR_SIZE_LIMIT + 1]), bulk("raw"));
+        assert_eq!(encoding(&[b'x'; 100]), bulk("raw"));
+
+        let missing = Command::ObjectEncoding {
+            key: "missing".to_string(),
+        };
+        assert_eq!(
+            missing.execute(&db, &mut Client::default()),
+            Err(CommandError::NoSuchKey)
+        );
+    }
+
+    #[test]
+    fn test_cluster_standalone_replies() {
+        let db = Db::new();
+        let mut client = Client::default();
+
+        match run(&db, &mut client, &["CLUSTER", "INFO"]) {
+            Frame::Bulk(Some(info)) => {
+                let info = String::from_utf8(info.to_vec()).unwrap();
+                assert!(info.lines().any(|line| line == "cluster_enabled:0"), "{}", info);
+            }
+            other => panic!("expected bulk, got {:?}", other),
+        }
+
+        let Frame::Bulk(Some(id)) = run(&db, &mut client, &["cluster", "myid"]) else {
+            panic!("expected a node id");
+        };
+        assert_eq!(id.len(), 40);
+        assert!(id.iter().all(u8::is_ascii_hexdigit));
+        assert_eq!(run(&db, &mut client, &["CLUSTER", "MYID"]), Frame::Bulk(Some(id)));
+        assert_ne!(db.node_id(), Db::new().node_id());
+
+        assert_eq!(run(&db, &mut client, &["CLUSTER", "SLOTS"]), Frame::Array(Some(Vec::new())));
+        assert_eq!(run(&db, &mut client, &["CLUSTER", "SHARDS"]), Frame::Array(Some(Vec::new())));
+        assert_eq!(
+            run(&db, &mut client, &["CLUSTER", "SLOTS", "extra"]),
+            Frame::from(CommandError::WrongArity("cluster|slots"))
+        );
+
+        let Frame::Bulk(Some(info)) = run(&db, &mut client, &["INFO", "cluster"]) else {
+            panic!("expected INFO text");
+        };
+        assert_eq!(info, &b"# Cluster\r\ncluster_enabled:0"[..]);
+    }
+
+    #[test]
+    fn test_scripting_is_refused_by_name() {
+        let db = Db::new();
+        let mut client = Client::default();
+
+        for args in [
+            &["EVAL", "return 1", "0"][..],
+            &["evalsha", "e0e1f9fabfc9d4800c877a703b823ac0578ff8db", "1", "k"],
+            &["EVAL_RO", "return 1", "0"],
+            &["EVALSHA_RO", "e0e1f9fabfc9d4800c877a703b823ac0578ff8db", "0"],
+            &["SCRIPT", "LOAD", "return 1"],
+        ] {
+            assert_eq!(run(&db, &mut client, args), Frame::from(CommandError::NoScripting));
+        }
+        assert_eq!(
+            Frame::from(CommandError::NoScripting).encode(),
+            b"-ERR This server does not support Lua scripting\r\n"
+        );
+    }
+
+    #[test]
+    fn test_auth() {
+        let db = Db::new();
+        let mut client = Client::new(1);
+        assert_eq!(
+            run(&db, &mut client, &["AUTH", "secret"]),
+            Frame::from(CommandError::AuthNotConfigured)
+        );
+
+        db.config_mut().requirepass = Some("secret".to_string());
+        client.authenticated = false;
+        assert_eq!(run(&db, &mut client, &["PING"]), Frame::from(CommandError::NoAuth));
+        assert_eq!(run(&db, &mut client, &["GET", "k"]), Frame::from(CommandError::NoAuth));
+        for args in [&["AUTH", "wrong"][..], &["AUTH", "admin", "secret"]] {
+            assert_eq!(run(&db, &mut client, args), Frame::from(CommandError::WrongPass));
+        }
+        assert_eq!(
+            run(&db, &mut client, &["AUTH", "a", "b", "c"]),
+            Frame::from(CommandError::Syntax)
+        );
+        assert_eq!(
+            run(&db, &mut client, &["AUTH", "default", "secret"]),
+            Frame::Simple("OK".to_string())
+        );
+        assert_eq!(run(&db, &mut client, &["PING"]), Frame::Simple("PONG".to_string()));
+    }
 } 
\ No newline at end of file
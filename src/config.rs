@@ -0,0 +1,391 @@
+use crate::glob::glob_match;
+use crate::resp::Limits;
+
+/// What to do when a write would take memory usage past `maxmemory`.
+///
+/// `allkeys-lfu` evicts the least frequently used keys to make room; the
+/// other policies refuse writes that may grow the dataset with an OOM error.
+/// The LFU policies also switch OBJECT from reporting idle time to access
+/// frequency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaxMemoryPolicy {
+    #[default]
+    NoEviction,
+    AllKeysLfu,
+    VolatileLfu,
+}
+
+impl MaxMemoryPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaxMemoryPolicy::NoEviction => "noeviction",
+            MaxMemoryPolicy::AllKeysLfu => "allkeys-lfu",
+            MaxMemoryPolicy::VolatileLfu => "volatile-lfu",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "noeviction" => Some(MaxMemoryPolicy::NoEviction),
+            "allkeys-lfu" => Some(MaxMemoryPolicy::AllKeysLfu),
+            "volatile-lfu" => Some(MaxMemoryPolicy::VolatileLfu),
+            _ => None,
+        }
+    }
+
+    /// Whether keys are ranked by access frequency rather than idle time.
+    pub fn is_lfu(&self) -> bool {
+        matches!(self, MaxMemoryPolicy::AllKeysLfu | MaxMemoryPolicy::VolatileLfu)
+    }
+}
+
+/// Server log verbosity, using the level names from redis.conf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogLevel {
+    Debug,
+    Verbose,
+    #[default]
+    Notice,
+    Warning,
+    Nothing,
+}
+
+impl LogLevel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Verbose => "verbose",
+            LogLevel::Notice => "notice",
+            LogLevel::Warning => "warning",
+            LogLevel::Nothing => "nothing",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "debug" => Some(LogLevel::Debug),
+            "verbose" => Some(LogLevel::Verbose),
+            "notice" => Some(LogLevel::Notice),
+            "warning" => Some(LogLevel::Warning),
+            "nothing" => Some(LogLevel::Nothing),
+            _ => None,
+        }
+    }
+
+    /// The most verbose `log` level that should be emitted at this setting.
+    pub fn filter(&self) -> log::LevelFilter {
+        match self {
+            LogLevel::Debug => log::LevelFilter::Trace,
+            LogLevel::Verbose => log::LevelFilter::Debug,
+            LogLevel::Notice => log::LevelFilter::Info,
+            LogLevel::Warning => log::LevelFilter::Warn,
+            LogLevel::Nothing => log::LevelFilter::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Memory limit in bytes for the dataset; 0 means unlimited.
+    pub maxmemory: usize,
+    pub maxmemory_policy: MaxMemoryPolicy,
+    /// Keys sampled per eviction; more is closer to true LFU but slower.
+    pub maxmemory_samples: usize,
+    pub loglevel: LogLevel,
+    /// Largest bulk string a client may send, in bytes.
+    pub proto_max_bulk_len: usize,
+    /// Most arguments a single command may carry.
+    pub max_command_args: usize,
+    /// Number of keyspace shards, rounded up to a power of two. Read once
+    /// when the [`Db`](crate::db::Db) is created.
+    pub db_shards: usize,
+    /// Whether DEBUG may be run at all; off by default, as in Redis.
+    pub enable_debug_command: bool,
+    /// How many hits it takes to saturate a key's LFU counter.
+    pub lfu_log_factor: u32,
+    /// Minutes of idleness that cost a key one point of LFU counter.
+    pub lfu_decay_time: u64,
+    /// Pending-connection queue length passed to `listen`; the kernel may
+    /// cap it further (`somaxconn` on Linux). Read once at bind time.
+    pub tcp_backlog: u32,
+    /// Port for the HTTP listener serving `/metrics`; 0 leaves it off. Read
+    /// once at bind time.
+    pub metrics_port: u16,
+    /// Password clients must AUTH with before anything else; `None` leaves
+    /// the server open. Checked when a client connects, so setting it
+    /// doesn't lock out connections that are already open.
+    pub requirepass: Option<String>,
+}
+
+/// DashMap's own default: four shards per core, so writers to disjoint
+/// keys rarely share a lock.
+fn default_db_shards() -> usize {
+    let cores = std::thread::available_parallelism().map_or(1, usize::from);
+    (cores * 4).next_power_of_two()
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            maxmemory: 0,
+            maxmemory_policy: MaxMemoryPolicy::default(),
+            maxmemory_samples: 5,
+            loglevel: LogLevel::default(),
+            proto_max_bulk_len: Limits::default().max_bulk_len,
+            max_command_args: Limits::default().max_array_len,
+            db_shards: default_db_shards(),
+            enable_debug_command: false,
+            lfu_log_factor: 10,
+            lfu_decay_time: 1,
+            tcp_backlog: 511,
+            metrics_port: 0,
+            requirepass: None,
+        }
+    }
+}
+
+/// A parameter reachable through CONFIG GET/SET.
+struct Param {
+    name: &'static str,
+    get: fn(&Config) -> String,
+    set: fn(&mut Config, &str) -> Result<(), &'static str>,
+}
+
+/// Every runtime-visible parameter. CONFIG GET and CONFIG SET both go
+/// through this table, so adding an entry here is all a new one needs.
+const PARAMS: &[Param] = &[
+    Param {
+        name: "maxmemory",
+        get: |config| config.maxmemory.to_string(),
+        set: |config, value| {
+            config.maxmemory = parse_memory(value).ok_or("argument must be a memory value")?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "maxmemory-policy",
+        get: |config| config.maxmemory_policy.as_str().to_string(),
+        set: |config, value| {
+            config.maxmemory_policy =
+                MaxMemoryPolicy::parse(value)
+                    .ok_or("argument must be one of: noeviction, allkeys-lfu, volatile-lfu")?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "maxmemory-samples",
+        get: |config| config.maxmemory_samples.to_string(),
+        set: |config, value| {
+            let samples = value
+                .parse()
+                .map_err(|_| "argument couldn't be parsed into an integer")?;
+            if !(1..=64).contains(&samples) {
+                return Err("argument must be between 1 and 64 inclusive");
+            }
+            config.maxmemory_samples = samples;
+            Ok(())
+        },
+    },
+    Param {
+        name: "loglevel",
+        get: |config| config.loglevel.as_str().to_string(),
+        set: |config, value| {
+            config.loglevel = LogLevel::parse(value)
+                .ok_or("argument must be one of: debug, verbose, notice, warning, nothing")?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "proto-max-bulk-len",
+        get: |config| config.proto_max_bulk_len.to_string(),
+        set: |config, value| {
+            let len = parse_memory(value).ok_or("argument must be a memory value")?;
+            // Same floor as Redis, so the limit can't lock out CONFIG SET itself.
+            if len < 1024 * 1024 {
+                return Err("argument must be between 1048576 and unlimited");
+            }
+            config.proto_max_bulk_len = len;
+            Ok(())
+        },
+    },
+    Param {
+        name: "max-command-args",
+        get: |config| config.max_command_args.to_string(),
+        set: |config, value| {
+            let max = value
+                .parse()
+                .map_err(|_| "argument couldn't be parsed into an integer")?;
+            // Like proto-max-bulk-len, never low enough to lock out CONFIG SET.
+            if max < 1024 {
+                return Err("argument must be between 1024 and unlimited");
+            }
+            config.max_command_args = max;
+            Ok(())
+        },
+    },
+    Param {
+        name: "db-shards",
+        get: |config| config.db_shards.to_string(),
+        set: |_, _| Err("can't set immutable config"),
+    },
+    Param {
+        name: "enable-debug-command",
+        get: |config| if config.enable_debug_command { "yes" } else { "no" }.to_string(),
+        set: |_, _| Err("can't set immutable config"),
+    },
+    Param {
+        name: "lfu-log-factor",
+        get: |config| config.lfu_log_factor.to_string(),
+        set: |config, value| {
+            config.lfu_log_factor = value
+                .parse()
+                .map_err(|_| "argument couldn't be parsed into an integer")?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "lfu-decay-time",
+        get: |config| config.lfu_decay_time.to_string(),
+        set: |config, value| {
+            config.lfu_decay_time = value
+                .parse()
+                .map_err(|_| "argument couldn't be parsed into an integer")?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "tcp-backlog",
+        get: |config| config.tcp_backlog.to_string(),
+        set: |_, _| Err("can't set immutable config"),
+    },
+    Param {
+        name: "metrics-port",
+        get: |config| config.metrics_port.to_string(),
+        set: |_, _| Err("can't set immutable config"),
+    },
+    Param {
+        name: "requirepass",
+        get: |config| config.requirepass.clone().unwrap_or_default(),
+        set: |config, value| {
+            // As in Redis, an empty password turns authentication off.
+            config.requirepass = (!value.is_empty()).then(|| value.to_string());
+            Ok(())
+        },
+    },
+];
+
+impl Config {
+    /// Parser bounds derived from the current settings.
+    pub fn limits(&self) -> Limits {
+        Limits {
+            max_bulk_len: self.proto_max_bulk_len,
+            max_array_len: self.max_command_args,
+        }
+    }
+
+    /// Returns every parameter whose name matches the glob `pattern`, in
+    /// registry order, as `(name, value)` pairs.
+    pub fn get(&self, pattern: &str) -> Vec<(&'static str, String)> {
+        PARAMS
+            .iter()
+            .filter(|param| glob_match(pattern.as_bytes(), param.name.as_bytes(), true))
+            .map(|param| (param.name, (param.get)(self)))
+            .collect()
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let param = PARAMS
+            .iter()
+            .find(|param| param.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown option or number of arguments for CONFIG SET - '{}'",
+                    name
+                )
+            })?;
+
+        (param.set)(self, value).map_err(|e| {
+            format!(
+                "CONFIG SET failed (possibly related to argument '{}') - {}",
+                param.name, e
+            )
+        })
+    }
+}
+
+/// Parses a memory amount such as `100`, `10kb` or `1gb`, using the same
+/// units as redis.conf (`k`/`m`/`g` are powers of 1000, `kb`/`mb`/`gb` of 1024).
+fn parse_memory(value: &str) -> Option<usize> {
+    let value = value.to_lowercase();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (digits, unit) = value.split_at(split);
+    let multiplier = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    digits.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_glob() {
+        let config = Config::default();
+        let names: Vec<_> = config.get("*").into_iter().map(|(name, _)| name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "maxmemory",
+                "maxmemory-policy",
+                "maxmemory-samples",
+                "loglevel",
+                "proto-max-bulk-len",
+                "max-command-args",
+                "db-shards",
+                "enable-debug-command",
+                "lfu-log-factor",
+                "lfu-decay-time",
+                "tcp-backlog",
+                "metrics-port",
+                "requirepass",
+            ]
+        );
+        assert_eq!(
+            config.get("maxmemory"),
+            vec![("maxmemory", "0".to_string())]
+        );
+        assert!(config.get("nothing*").is_empty());
+    }
+
+    #[test]
+    fn test_set() {
+        let mut config = Config::default();
+        config.set("MAXMEMORY", "10mb").unwrap();
+        assert_eq!(config.maxmemory, 10 * 1024 * 1024);
+        assert!(config.set("maxmemory", "lots").is_err());
+        assert!(config.set("maxmemory-policy", "allkeys-random").is_err());
+        assert!(config.set("maxmemory-samples", "0").is_err());
+        config.set("maxmemory-samples", "10").unwrap();
+        assert_eq!(config.maxmemory_samples, 10);
+        assert!(config.set("no-such-option", "1").is_err());
+        assert!(config.set("proto-max-bulk-len", "1kb").is_err());
+        config.set("requirepass", "secret").unwrap();
+        assert_eq!(config.requirepass.as_deref(), Some("secret"));
+        config.set("requirepass", "").unwrap();
+        assert_eq!(config.requirepass, None);
+        assert_eq!(
+            config.set("db-shards", "8").unwrap_err(),
+            "CONFIG SET failed (possibly related to argument 'db-shards') - can't set immutable config"
+        );
+    }
+}
@@ -1,16 +1,148 @@
+use bytes::Bytes;
+use dashmap::mapref::entry::Entry as MapEntry;
 use dashmap::DashMap;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::{Config, MaxMemoryPolicy};
+use crate::lfu;
+use crate::resp::parse_decimal;
+use crate::pause::Pause;
+use crate::stats::Stats;
+
+/// Why an INCR-family update was refused; the stored value is left as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncrError {
+    NotInteger,
+    Overflow,
+}
+
+/// When SET may write: unconditionally, or only with NX / XX.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCondition {
+    Always,
+    IfAbsent,
+    IfPresent,
+}
+
+struct Entry {
+    /// Shared with replies in flight, so a read only bumps a refcount under
+    /// the shard lock and large values are written out without a copy.
+    value: Bytes,
+    /// Last time the key was read or written, in Unix milliseconds, reported
+    /// by OBJECT IDLETIME. This and `freq` are atomic so a read can touch
+    /// the key under the shard's shared lock.
+    last_access: AtomicU64,
+    /// Logarithmic access counter, reported by OBJECT FREQ; see [`lfu`].
+    freq: AtomicU8,
+}
+
+/// How far into a shard an eviction sample may land. Shards can only be
+/// walked, not indexed, so this keeps each sample O(1) in the keyspace size.
+const SAMPLE_WINDOW: usize = 64;
+
+/// Work handed to the background reclaim thread.
+type Reclaim = Box<dyn FnOnce() + Send>;
+
+/// The LFU tuning knobs, copied out of [`LfuKnobs`] before taking a shard
+/// lock.
+#[derive(Clone, Copy)]
+struct LfuParams {
+    log_factor: u32,
+    decay_time: u64,
+}
+
+/// The LFU settings cached outside the config lock, since every access
+/// reads them. Kept in step by [`Db::set_config`].
+struct LfuKnobs {
+    log_factor: AtomicU32,
+    decay_time: AtomicU64,
+}
+
+impl LfuKnobs {
+    fn store(&self, config: &Config) {
+        self.log_factor.store(config.lfu_log_factor, Ordering::Relaxed);
+        self.decay_time.store(config.lfu_decay_time, Ordering::Relaxed);
+    }
+}
+
+/// Wall-clock milliseconds, the unit of an entry's access time.
+fn mstime() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+impl Entry {
+    fn new(value: Bytes) -> Self {
+        Self {
+            value,
+            last_access: AtomicU64::new(mstime()),
+            freq: AtomicU8::new(lfu::LFU_INIT_VAL),
+        }
+    }
+
+    fn idle_time(&self) -> Duration {
+        let last_access = self.last_access.load(Ordering::Relaxed);
+        Duration::from_millis(mstime().saturating_sub(last_access))
+    }
+
+    /// The LFU counter with idle decay applied.
+    fn freq(&self, decay_time: u64) -> u8 {
+        lfu::decay(self.freq.load(Ordering::Relaxed), self.idle_time(), decay_time)
+    }
+
+    /// Records an access for both the idle clock and the LFU counter. Two
+    /// readers racing here may count one hit between them, which the
+    /// approximate counter tolerates.
+    fn touch(&self, lfu: LfuParams) {
+        let freq = lfu::log_incr(self.freq(lfu.decay_time), lfu.log_factor);
+        self.freq.store(freq, Ordering::Relaxed);
+        self.last_access.store(mstime(), Ordering::Relaxed);
+    }
+}
+
+/// Every `RandomState` is freshly seeded by std, which is all the
+/// randomness a node id needs.
+fn random_node_id() -> String {
+    use std::hash::{BuildHasher, RandomState};
+
+    let mut id = String::with_capacity(48);
+    for _ in 0..3 {
+        id.push_str(&format!("{:016x}", RandomState::new().hash_one(0u8)));
+    }
+    id.truncate(40);
+    id
+}
 
 #[derive(Clone)]
 pub struct Db {
-    data: Arc<DashMap<String, Vec<u8>>>,
+    data: Arc<DashMap<String, Entry>>,
+    config: Arc<RwLock<Config>>,
+    lfu: Arc<LfuKnobs>,
+    /// The `loglevel` setting as a `log::LevelFilter`, cached outside the
+    /// config lock since every log call checks it.
+    log_filter: Arc<AtomicUsize>,
+    /// Approximate dataset size: the sum of all key and value lengths.
+    used_memory: Arc<AtomicUsize>,
+    /// High-water mark of `used_memory`, reported by MEMORY STATS.
+    peak_memory: Arc<AtomicUsize>,
+    stats: Arc<Stats>,
+    pause: Arc<Pause>,
+    /// Random 40-character hex identity, reported by CLUSTER MYID.
+    node_id: Arc<str>,
+    /// Last id handed to a connection, shared by every listener.
+    last_client_id: Arc<AtomicU64>,
+    /// Queue to the thread that frees lazily flushed shards, started on the
+    /// first FLUSHALL ASYNC. It exits once every clone of the Db is gone.
+    reclaim: Arc<OnceLock<mpsc::Sender<Reclaim>>>,
 }
 
 impl Default for Db {
     fn default() -> Self {
-        Self {
-            data: Arc::new(DashMap::new()),
-        }
+        Self::with_config(Config::default())
     }
 }
 
@@ -19,31 +151,349 @@ impl Db {
         Self::default()
     }
 
-    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
-        self.data.get(key).map(|v| v.clone())
+    pub fn with_config(config: Config) -> Self {
+        let lfu = LfuKnobs {
+            log_factor: AtomicU32::new(config.lfu_log_factor),
+            decay_time: AtomicU64::new(config.lfu_decay_time),
+        };
+        let log_filter = config.loglevel.filter() as usize;
+        Self {
+            // DashMap panics unless the shard count is a power of two above 1.
+            data: Arc::new(DashMap::with_shard_amount(
+                config.db_shards.max(2).next_power_of_two(),
+            )),
+            config: Arc::new(RwLock::new(config)),
+            lfu: Arc::new(lfu),
+            log_filter: Arc::new(AtomicUsize::new(log_filter)),
+            used_memory: Arc::new(AtomicUsize::new(0)),
+            peak_memory: Arc::new(AtomicUsize::new(0)),
+            stats: Arc::new(Stats::default()),
+            pause: Arc::new(Pause::default()),
+            node_id: random_node_id().into(),
+            last_client_id: Arc::new(AtomicU64::new(0)),
+            reclaim: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Bytes> {
+        self.get_with(key, true)
     }
 
-    pub fn set(&self, key: String, value: Vec<u8>) {
-        self.data.insert(key, value);
+    /// Reads a value; with `touch` false the read leaves the key's access
+    /// time alone, for clients in CLIENT NO-TOUCH mode.
+    pub fn get_with(&self, key: &str, touch: bool) -> Option<Bytes> {
+        let lfu = self.lfu_params();
+        self.data.get(key).map(|entry| {
+            if touch {
+                entry.touch(lfu);
+            }
+            entry.value.clone()
+        })
+    }
+
+    pub fn set(&self, key: String, value: impl Into<Bytes>) {
+        self.set_with(key, value, SetCondition::Always);
+    }
+
+    /// Stores `value` if `condition` holds for the key's current state, and
+    /// returns whether it was written along with the previous value. The
+    /// check and write happen under the key's shard lock.
+    pub fn set_with(
+        &self,
+        key: String,
+        value: impl Into<Bytes>,
+        condition: SetCondition,
+    ) -> (bool, Option<Bytes>) {
+        let value = value.into();
+        let lfu = self.lfu_params();
+        match self.data.entry(key) {
+            MapEntry::Occupied(mut occupied) => {
+                if condition == SetCondition::IfAbsent {
+                    return (false, Some(occupied.get().value.clone()));
+                }
+                let entry = occupied.get_mut();
+                self.resize_used_memory(entry.value.len(), value.len());
+                let old = std::mem::replace(&mut entry.value, value);
+                entry.touch(lfu);
+                (true, Some(old))
+            }
+            MapEntry::Vacant(vacant) => {
+                if condition == SetCondition::IfPresent {
+                    return (false, None);
+                }
+                self.grow_used_memory(vacant.key().len() + value.len());
+                vacant.insert(Entry::new(value));
+                (true, None)
+            }
+        }
     }
 
     pub fn delete(&self, key: &str) -> bool {
-        self.data.remove(key).is_some()
+        self.remove(key).is_some()
+    }
+
+    /// Deletes the key and hands back its value.
+    pub fn remove(&self, key: &str) -> Option<Bytes> {
+        let (key, entry) = self.data.remove(key)?;
+        self.used_memory
+            .fetch_sub(key.len() + entry.value.len(), Ordering::Relaxed);
+        Some(entry.value)
+    }
+
+    /// Adds `delta` to the integer stored at `key` (0 if missing) and returns
+    /// the result. The check and update happen under the key's shard lock, so
+    /// concurrent increments never lose updates.
+    pub fn incr_by(&self, key: &str, delta: i64) -> Result<i64, IncrError> {
+        let lfu = self.lfu_params();
+        match self.data.entry(key.to_string()) {
+            MapEntry::Occupied(mut occupied) => {
+                let entry = occupied.get_mut();
+                let current = parse_decimal(&entry.value).ok_or(IncrError::NotInteger)?;
+                let updated = current.checked_add(delta).ok_or(IncrError::Overflow)?;
+                let value = Bytes::from(updated.to_string());
+                self.resize_used_memory(entry.value.len(), value.len());
+                entry.value = value;
+                entry.touch(lfu);
+                Ok(updated)
+            }
+            MapEntry::Vacant(vacant) => {
+                let value = Bytes::from(delta.to_string());
+                self.grow_used_memory(key.len() + value.len());
+                vacant.insert(Entry::new(value));
+                Ok(delta)
+            }
+        }
+    }
+
+    pub fn exists(&self, key: &str) -> bool {
+        self.data.contains_key(key)
+    }
+
+    /// Time since the key was last accessed, without counting as an access.
+    pub fn idle_time(&self, key: &str) -> Option<Duration> {
+        self.data.get(key).map(|entry| entry.idle_time())
+    }
+
+    /// The key's LFU counter with idle decay applied, without counting as an
+    /// access.
+    pub fn freq(&self, key: &str) -> Option<u8> {
+        let decay_time = self.lfu_params().decay_time;
+        self.data.get(key).map(|entry| entry.freq(decay_time))
+    }
+
+    /// Moves the key's last access `by` into the past, so tests can check
+    /// idle time without sleeping.
+    #[cfg(test)]
+    pub(crate) fn backdate(&self, key: &str, by: Duration) {
+        if let Some(entry) = self.data.get(key) {
+            entry.last_access.fetch_sub(by.as_millis() as u64, Ordering::Relaxed);
+        }
+    }
+
+    fn lfu_params(&self) -> LfuParams {
+        LfuParams {
+            log_factor: self.lfu.log_factor.load(Ordering::Relaxed),
+            decay_time: self.lfu.decay_time.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn config(&self) -> RwLockReadGuard<'_, Config> {
+        self.config.read().unwrap()
+    }
+
+    /// Direct access to the config. The LFU and log level settings are
+    /// cached outside it, so changes to those must go through
+    /// [`Db::set_config`].
+    pub fn config_mut(&self) -> RwLockWriteGuard<'_, Config> {
+        self.config.write().unwrap()
+    }
+
+    /// Applies CONFIG SET and refreshes the settings cached outside the
+    /// config lock.
+    pub fn set_config(&self, name: &str, value: &str) -> Result<(), String> {
+        let mut config = self.config_mut();
+        config.set(name, value)?;
+        self.lfu.store(&config);
+        self.log_filter
+            .store(config.loglevel.filter() as usize, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Whether a message at `level` passes the `loglevel` setting.
+    pub fn log_enabled(&self, level: log::Level) -> bool {
+        level as usize <= self.log_filter.load(Ordering::Relaxed)
+    }
+
+    pub fn used_memory(&self) -> usize {
+        self.used_memory.load(Ordering::Relaxed)
+    }
+
+    pub fn peak_memory(&self) -> usize {
+        self.peak_memory.load(Ordering::Relaxed)
+    }
+
+    /// Removes every key by swapping each shard for an empty one. With
+    /// `lazy` the old shards are freed on a background thread, so the
+    /// caller's cost doesn't depend on the keyspace size; `used_memory`
+    /// then drops once they have been freed, as with Redis's lazyfree.
+    pub fn flush(&self, lazy: bool) {
+        for shard in self.data.shards() {
+            let detached = std::mem::take(&mut *shard.write());
+            let used_memory = self.used_memory.clone();
+            let reclaim = move || {
+                let bytes: usize = detached
+                    .iter()
+                    .map(|(key, entry)| key.len() + entry.get().value.len())
+                    .sum();
+                drop(detached);
+                used_memory.fetch_sub(bytes, Ordering::Relaxed);
+            };
+            if lazy {
+                // The thread only stops once every sender is dropped, and
+                // `self` holds one, so the send can't fail.
+                let _ = self.reclaimer().send(Box::new(reclaim));
+            } else {
+                reclaim();
+            }
+        }
+    }
+
+    fn reclaimer(&self) -> &mpsc::Sender<Reclaim> {
+        self.reclaim.get_or_init(|| {
+            let (tx, rx) = mpsc::channel::<Reclaim>();
+            std::thread::Builder::new()
+                .name("lazyfree".to_string())
+                .spawn(move || rx.into_iter().for_each(|reclaim| reclaim()))
+                .expect("failed to spawn the lazyfree thread");
+            tx
+        })
+    }
+
+    /// Number of keys in the keyspace.
+    pub fn dbsize(&self) -> usize {
+        self.data.len()
+    }
+
+    fn grow_used_memory(&self, bytes: usize) {
+        let used = self.used_memory.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak_memory.fetch_max(used, Ordering::Relaxed);
+    }
+
+    /// Accounts for a value of `old` bytes being replaced by one of `new`,
+    /// applying only the difference so an overwrite can't inflate the peak.
+    fn resize_used_memory(&self, old: usize, new: usize) {
+        if new >= old {
+            self.grow_used_memory(new - old);
+        } else {
+            self.used_memory.fetch_sub(old - new, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn pause(&self) -> &Pause {
+        &self.pause
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    /// A fresh id for a new connection, as reported by CLIENT ID; ids start
+    /// at 1 and are never reused.
+    pub fn next_client_id(&self) -> u64 {
+        self.last_client_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Evicts keys as `maxmemory-policy` allows until usage is back under
+    /// `maxmemory`, then returns [`is_oom`](Self::is_oom).
+    ///
+    /// `allkeys-lfu` removes keys with low decayed LFU counters, chosen from
+    /// `maxmemory-samples` random keys per eviction as in Redis.
+    /// `volatile-lfu` only considers keys with a TTL. No key can have one
+    /// yet, so it refuses writes just like `noeviction`.
+    pub fn enforce_maxmemory(&self) -> bool {
+        let (maxmemory, policy, samples) = {
+            let config = self.config();
+            (config.maxmemory, config.maxmemory_policy, config.maxmemory_samples)
+        };
+        if policy == MaxMemoryPolicy::AllKeysLfu {
+            while maxmemory > 0 && self.used_memory() > maxmemory {
+                if !self.evict_lfu(samples) {
+                    break;
+                }
+            }
+        }
+        self.is_oom()
+    }
+
+    /// Evicts the coldest of `samples` keys picked near a random spot in
+    /// the keyspace. Returns false if the keyspace is empty.
+    ///
+    /// Like Redis's `dictGetSomeKeys`, the sample walks forward from a
+    /// random shard and a random offset within its first [`SAMPLE_WINDOW`]
+    /// entries, skipping empty shards. Its cost is bounded by the sample and
+    /// shard counts rather than the keyspace size.
+    fn evict_lfu(&self, samples: usize) -> bool {
+        let decay_time = self.lfu_params().decay_time;
+        let shards = self.data.shards();
+        let start = lfu::random_index(shards.len());
+        let mut coldest: Option<(u8, String)> = None;
+        let mut sampled = 0;
+        for step in 0..shards.len() {
+            if sampled == samples {
+                break;
+            }
+            let shard = shards[(start + step) % shards.len()].read();
+            if shard.is_empty() {
+                continue;
+            }
+            let offset = lfu::random_index(shard.len().min(SAMPLE_WINDOW));
+            let picked = shard.iter().skip(offset).chain(shard.iter().take(offset));
+            for (key, entry) in picked.take(samples - sampled) {
+                let freq = entry.get().freq(decay_time);
+                if coldest.as_ref().is_none_or(|(f, _)| freq < *f) {
+                    coldest = Some((freq, key.clone()));
+                }
+                sampled += 1;
+            }
+        }
+
+        let Some((_, key)) = coldest else {
+            return false;
+        };
+        if self.delete(&key) {
+            self.stats.record_eviction();
+        }
+        true
+    }
+
+    /// Returns true when a command that may grow the dataset must be refused
+    /// because usage has gone past `maxmemory`.
+    pub fn is_oom(&self) -> bool {
+        let config = self.config();
+        config.maxmemory > 0 && self.used_memory() > config.maxmemory
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::LogLevel;
+    use std::time::Instant;
 
     #[test]
     fn test_set_get() {
         let db = Db::new();
         let key = "test_key".to_string();
-        let value = b"test_value".to_vec();
+        let value = Bytes::from_static(b"test_value");
         
         db.set(key.clone(), value.clone());
-        assert_eq!(db.get(&key), Some(value));
+        assert_eq!(db.get(&key), Some(value.clone()));
+        // Reads share the stored buffer rather than copying it.
+        assert_eq!(db.get(&key).unwrap().as_ptr(), value.as_ptr());
     }
 
     #[test]
@@ -68,7 +518,7 @@ mod tests {
             let db = db.clone();
             let handle = thread::spawn(move || {
                 let key = format!("key_{}", i);
-                let value = format!("value_{}", i).into_bytes();
+                let value = Bytes::from(format!("value_{}", i));
                 db.set(key.clone(), value.clone());
                 assert_eq!(db.get(&key), Some(value));
             });
@@ -79,4 +529,201 @@ mod tests {
             handle.join().unwrap();
         }
     }
+
+    #[test]
+    fn test_used_memory_tracking() {
+        let db = Db::new();
+        db.set("key".to_string(), b"value".to_vec());
+        assert_eq!(db.used_memory(), 8);
+
+        db.set("key".to_string(), b"v".to_vec());
+        assert_eq!(db.used_memory(), 4);
+
+        db.delete("key");
+        assert_eq!(db.used_memory(), 0);
+    }
+
+    #[test]
+    fn test_incr_by() {
+        let db = Db::new();
+        assert_eq!(db.incr_by("counter", 5), Ok(5));
+        assert_eq!(db.incr_by("counter", -7), Ok(-2));
+        assert_eq!(db.get("counter"), Some(Bytes::from_static(b"-2")));
+        assert_eq!(db.used_memory(), "counter".len() + 2);
+
+        db.set("text".to_string(), b"abc".to_vec());
+        assert_eq!(db.incr_by("text", 1), Err(IncrError::NotInteger));
+        db.set("spaced".to_string(), b" 1".to_vec());
+        assert_eq!(db.incr_by("spaced", 1), Err(IncrError::NotInteger));
+    }
+
+    #[test]
+    fn test_concurrent_incr() {
+        use std::thread;
+
+        let db = Arc::new(Db::new());
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let db = db.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        db.incr_by("counter", 1).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(db.get("counter"), Some(Bytes::from_static(b"8000")));
+    }
+
+    #[test]
+    fn test_peak_memory() {
+        let db = Db::new();
+        db.set("key".to_string(), vec![0; 100]);
+        db.delete("key");
+        assert_eq!(db.used_memory(), 0);
+        assert_eq!(db.peak_memory(), 103);
+        assert_eq!(db.dbsize(), 0);
+    }
+
+    #[test]
+    fn test_client_ids_are_shared() {
+        let db = Db::new();
+        let other = db.clone();
+        assert_eq!(db.next_client_id(), 1);
+        assert_eq!(other.next_client_id(), 2);
+        assert_eq!(db.next_client_id(), 3);
+    }
+
+    #[test]
+    fn test_overwrite_keeps_peak() {
+        let db = Db::new();
+        db.set("key".to_string(), vec![0; 100]);
+        db.set("key".to_string(), vec![0; 100]);
+        db.set("key".to_string(), vec![0; 50]);
+        assert_eq!(db.used_memory(), 53);
+        assert_eq!(db.peak_memory(), 103);
+
+        db.set("n".to_string(), b"99".to_vec());
+        db.incr_by("n", 1).unwrap();
+        assert_eq!(db.used_memory(), 53 + 4);
+        assert_eq!(db.peak_memory(), 103);
+    }
+
+    /// Write throughput on disjoint keys by shard count; run with
+    /// `cargo test --release -- --ignored --nocapture bench_shard_count`.
+    #[test]
+    #[ignore]
+    fn bench_shard_count() {
+        use std::thread;
+        use std::time::Instant;
+
+        let threads = thread::available_parallelism().map_or(4, usize::from);
+        const WRITES: usize = 200_000;
+        for shards in [2, 8, 32, 128] {
+            let db = Arc::new(Db::with_config(Config {
+                db_shards: shards,
+                ..Config::default()
+            }));
+            let start = Instant::now();
+            let handles: Vec<_> = (0..threads)
+                .map(|t| {
+                    let db = db.clone();
+                    thread::spawn(move || {
+                        for i in 0..WRITES {
+                            db.set(format!("{}:{}", t, i % 1024), b"v".to_vec());
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            let elapsed = start.elapsed();
+            let ops = (threads * WRITES) as f64 / elapsed.as_secs_f64();
+            println!("{:>3} shards, {} threads: {:.0} writes/s", shards, threads, ops);
+        }
+    }
+
+    #[test]
+    fn test_allkeys_lfu_evicts_coldest() {
+        let db = Db::with_config(Config {
+            maxmemory_policy: MaxMemoryPolicy::AllKeysLfu,
+            ..Config::default()
+        });
+        db.set("hot".to_string(), vec![0; 10]);
+        db.set("cold".to_string(), vec![0; 10]);
+        for _ in 0..50 {
+            db.get("hot");
+        }
+
+        db.config_mut().maxmemory = 20;
+        assert!(!db.enforce_maxmemory());
+        assert_eq!(db.get("cold"), None);
+        assert!(db.get("hot").is_some());
+        assert_eq!(db.stats().evicted_keys(), 1);
+        assert_eq!(db.used_memory(), 13);
+    }
+
+    #[test]
+    fn test_lfu_settings_follow_config_set() {
+        let db = Db::new();
+        db.set("key".to_string(), b"value".to_vec());
+        // A log factor of 0 makes every hit count.
+        db.set_config("lfu-log-factor", "0").unwrap();
+        for _ in 0..10 {
+            db.get("key");
+        }
+        assert_eq!(db.freq("key"), Some(lfu::LFU_INIT_VAL + 10));
+
+        db.get_with("key", false);
+        assert_eq!(db.freq("key"), Some(lfu::LFU_INIT_VAL + 10));
+    }
+
+    #[test]
+    fn test_log_enabled_follows_config_set() {
+        let db = Db::with_config(Config {
+            loglevel: LogLevel::Warning,
+            ..Config::default()
+        });
+        assert!(db.log_enabled(log::Level::Warn));
+        assert!(!db.log_enabled(log::Level::Info));
+
+        db.set_config("loglevel", "debug").unwrap();
+        assert!(db.log_enabled(log::Level::Trace));
+        db.set_config("loglevel", "nothing").unwrap();
+        assert!(!db.log_enabled(log::Level::Error));
+    }
+
+    #[test]
+    fn test_volatile_lfu_without_ttls_refuses() {
+        let db = Db::with_config(Config {
+            maxmemory: 1,
+            maxmemory_policy: MaxMemoryPolicy::VolatileLfu,
+            ..Config::default()
+        });
+        db.set("key".to_string(), b"value".to_vec());
+        assert!(db.enforce_maxmemory());
+        assert!(db.exists("key"));
+    }
+
+    #[test]
+    fn test_flush() {
+        for lazy in [false, true] {
+            let db = Db::new();
+            for i in 0..1000 {
+                db.set(format!("key{i}"), vec![0; 100]);
+            }
+            db.flush(lazy);
+            assert_eq!(db.dbsize(), 0);
+            assert_eq!(db.get("key0"), None);
+            let start = Instant::now();
+            while db.used_memory() > 0 {
+                assert!(start.elapsed() < Duration::from_secs(1));
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
 } 
\ No newline at end of file
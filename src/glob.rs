@@ -0,0 +1,170 @@
+/// Deepest `*` recursion followed before giving up, as in Redis.
+const MAX_NESTING: usize = 1000;
+
+/// Glob-style matching with the same rules as Redis' `stringmatchlen`:
+/// `*`, `?`, `[...]` classes (with `^` negation and `a-z` ranges) and `\`
+/// to escape the next character.
+pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    matches(pattern, string, nocase, &mut false, 0)
+}
+
+/// Matches `pattern` against `string` from `*` recursion level `nesting`.
+///
+/// Once the rest of the pattern after some `*` fails to match at every
+/// offset, no earlier `*` can help by consuming more of the string, so
+/// `skip_longer` is set and the whole search unwinds. Without this,
+/// patterns like `*a*a*a*b` take exponential time.
+fn matches(
+    pattern: &[u8],
+    string: &[u8],
+    nocase: bool,
+    skip_longer: &mut bool,
+    nesting: usize,
+) -> bool {
+    if nesting > MAX_NESTING {
+        return false;
+    }
+    let eq = |a: u8, b: u8| {
+        if nocase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+
+    let (mut p, mut s) = (0, 0);
+    while p < pattern.len() {
+        match pattern[p] {
+            b'*' => {
+                while p + 1 < pattern.len() && pattern[p + 1] == b'*' {
+                    p += 1;
+                }
+                if p + 1 == pattern.len() {
+                    return true;
+                }
+                for i in s..=string.len() {
+                    if matches(&pattern[p + 1..], &string[i..], nocase, skip_longer, nesting + 1) {
+                        return true;
+                    }
+                    if *skip_longer {
+                        return false;
+                    }
+                }
+                *skip_longer = true;
+                return false;
+            }
+            b'?' => {
+                if s == string.len() {
+                    return false;
+                }
+                s += 1;
+            }
+            b'[' => {
+                if s == string.len() {
+                    return false;
+                }
+                let c = string[s];
+                p += 1;
+                let negate = pattern.get(p) == Some(&b'^');
+                if negate {
+                    p += 1;
+                }
+
+                let mut matched = false;
+                while p < pattern.len() && pattern[p] != b']' {
+                    if pattern[p] == b'\\' && p + 1 < pattern.len() {
+                        p += 1;
+                        matched |= eq(pattern[p], c);
+                    } else if p + 2 < pattern.len() && pattern[p + 1] == b'-' {
+                        let (mut lo, mut hi, mut ch) = (pattern[p], pattern[p + 2], c);
+                        if nocase {
+                            lo = lo.to_ascii_lowercase();
+                            hi = hi.to_ascii_lowercase();
+                            ch = ch.to_ascii_lowercase();
+                        }
+                        if lo > hi {
+                            std::mem::swap(&mut lo, &mut hi);
+                        }
+                        matched |= (lo..=hi).contains(&ch);
+                        p += 2;
+                    } else {
+                        matched |= eq(pattern[p], c);
+                    }
+                    p += 1;
+                }
+
+                if matched == negate {
+                    return false;
+                }
+                s += 1;
+                // An unterminated class consumes the rest of the pattern.
+                if p == pattern.len() {
+                    break;
+                }
+            }
+            b'\\' if p + 1 < pattern.len() => {
+                p += 1;
+                if s == string.len() || !eq(pattern[p], string[s]) {
+                    return false;
+                }
+                s += 1;
+            }
+            c => {
+                if s == string.len() || !eq(c, string[s]) {
+                    return false;
+                }
+                s += 1;
+            }
+        }
+        p += 1;
+    }
+
+    s == string.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcards() {
+        assert!(glob_match(b"*", b"", false));
+        assert!(glob_match(b"*", b"anything", false));
+        assert!(glob_match(b"max*", b"maxmemory", false));
+        assert!(glob_match(b"*memory*", b"maxmemory-policy", false));
+        assert!(!glob_match(b"max*", b"minmemory", false));
+        assert!(glob_match(b"h?llo", b"hello", false));
+        assert!(!glob_match(b"h?llo", b"hllo", false));
+    }
+
+    #[test]
+    fn test_classes() {
+        assert!(glob_match(b"h[ae]llo", b"hallo", false));
+        assert!(!glob_match(b"h[ae]llo", b"hillo", false));
+        assert!(glob_match(b"h[^e]llo", b"hallo", false));
+        assert!(!glob_match(b"h[^e]llo", b"hello", false));
+        assert!(glob_match(b"h[a-c]llo", b"hbllo", false));
+        assert!(!glob_match(b"h[a-c]llo", b"hdllo", false));
+    }
+
+    #[test]
+    fn test_escape_and_case() {
+        assert!(glob_match(b"h\\*llo", b"h*llo", false));
+        assert!(!glob_match(b"h\\*llo", b"hello", false));
+        assert!(glob_match(b"MAX*", b"maxmemory", true));
+        assert!(!glob_match(b"MAX*", b"maxmemory", false));
+    }
+
+    #[test]
+    fn test_pathological_patterns() {
+        // Exponential without the early exit.
+        let string = vec![b'a'; 10_000];
+        let pattern = b"*a".repeat(30);
+        assert!(!glob_match(&[&pattern[..], b"b"].concat(), &string, false));
+        assert!(glob_match(&pattern, &string, false));
+
+        // Each `*` is a level of recursion; past the limit nothing matches.
+        assert!(glob_match(&b"*a".repeat(MAX_NESTING), &string, false));
+        assert!(!glob_match(&b"*a".repeat(MAX_NESTING + 2), &string, false));
+    }
+}
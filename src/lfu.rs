@@ -0,0 +1,85 @@
+//! Redis's approximate LFU counter: an 8-bit access count that grows
+//! logarithmically with hits and decays while the key sits idle.
+
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Counter given to new keys, so they aren't the first evicted just for
+/// being new.
+pub(crate) const LFU_INIT_VAL: u8 = 5;
+
+/// Records one hit. The chance of incrementing shrinks as the counter grows,
+/// so 255 stands for roughly a million hits at the default `log_factor`.
+pub(crate) fn log_incr(counter: u8, log_factor: u32) -> u8 {
+    if counter == u8::MAX {
+        return counter;
+    }
+    let base = f64::from(counter.saturating_sub(LFU_INIT_VAL));
+    let p = 1.0 / (base * f64::from(log_factor) + 1.0);
+    if random_unit() < p {
+        counter + 1
+    } else {
+        counter
+    }
+}
+
+/// The counter after losing one point per `decay_time` minutes of `idle`;
+/// a `decay_time` of 0 disables decay.
+pub(crate) fn decay(counter: u8, idle: Duration, decay_time: u64) -> u8 {
+    if decay_time == 0 {
+        return counter;
+    }
+    let periods = idle.as_secs() / 60 / decay_time;
+    counter.saturating_sub(periods.min(u64::from(u8::MAX)) as u8)
+}
+
+/// A uniform index in `0..len`; `len` must be non-zero.
+pub(crate) fn random_index(len: usize) -> usize {
+    ((random_unit() * len as f64) as usize).min(len - 1)
+}
+
+/// A uniform value in `[0, 1)` from a per-thread xorshift generator.
+fn random_unit() -> f64 {
+    thread_local! {
+        static STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+    }
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_incr() {
+        // The first hit past the initial value always counts.
+        assert_eq!(log_incr(LFU_INIT_VAL, 10), LFU_INIT_VAL + 1);
+        assert_eq!(log_incr(0, 10), 1);
+        assert_eq!(log_incr(u8::MAX, 10), u8::MAX);
+
+        let mut counter = LFU_INIT_VAL;
+        for _ in 0..10_000 {
+            counter = log_incr(counter, 10);
+        }
+        assert!(counter > LFU_INIT_VAL + 5 && counter < 100, "{}", counter);
+    }
+
+    #[test]
+    fn test_decay() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(decay(10, minute * 3, 1), 7);
+        assert_eq!(decay(10, minute * 3, 2), 9);
+        assert_eq!(decay(10, minute * 59, 1), 0);
+        assert_eq!(decay(10, minute * 59, 0), 10);
+        assert_eq!(decay(10, Duration::from_secs(59), 1), 10);
+    }
+}
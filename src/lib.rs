@@ -0,0 +1,19 @@
+//! A minimal Redis clone: a RESP server backed by an in-memory keyspace.
+//!
+//! The binary in `main.rs` runs it on port 6379; embedders can start their
+//! own instance through [`Server::builder`].
+
+pub mod client;
+pub mod command;
+pub mod config;
+pub mod db;
+pub mod pause;
+pub mod resp;
+pub mod stats;
+
+mod glob;
+mod lfu;
+mod metrics;
+mod server;
+
+pub use server::{run_server, Server, ServerBuilder};
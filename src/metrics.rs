@@ -0,0 +1,122 @@
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::db::Db;
+
+/// Longest request head accepted before the connection is dropped; scrapers
+/// send a few hundred bytes at most.
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// Renders the INFO counters in the Prometheus text exposition format.
+pub fn render(db: &Db) -> String {
+    let stats = db.stats();
+    let config = db.config();
+    let metrics: [(&str, &str, &str, u64); 8] = [
+        (
+            "redis_commands_processed_total",
+            "counter",
+            "Commands dispatched to execute.",
+            stats.commands_processed(),
+        ),
+        (
+            "redis_net_input_bytes_total",
+            "counter",
+            "Bytes read from clients.",
+            stats.net_input_bytes(),
+        ),
+        (
+            "redis_net_output_bytes_total",
+            "counter",
+            "Bytes written to clients.",
+            stats.net_output_bytes(),
+        ),
+        (
+            "redis_evicted_keys_total",
+            "counter",
+            "Keys evicted by maxmemory.",
+            stats.evicted_keys(),
+        ),
+        (
+            "redis_connected_clients",
+            "gauge",
+            "Client connections currently open.",
+            stats.connected_clients(),
+        ),
+        (
+            "redis_memory_used_bytes",
+            "gauge",
+            "Estimated dataset size.",
+            db.used_memory() as u64,
+        ),
+        (
+            "redis_memory_max_bytes",
+            "gauge",
+            "The maxmemory setting; 0 means unlimited.",
+            config.maxmemory as u64,
+        ),
+        ("redis_db_keys", "gauge", "Keys in the keyspace.", db.dbsize() as u64),
+    ];
+
+    let mut out = String::new();
+    for (name, kind, help, value) in metrics {
+        out.push_str(&format!("# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"));
+    }
+    out
+}
+
+/// Answers one HTTP request and closes the connection. Only `GET /metrics`
+/// is served; anything else gets a 404 or 405.
+pub async fn serve(mut socket: TcpStream, db: &Db) -> io::Result<()> {
+    let mut head = Vec::with_capacity(1024);
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        if head.len() >= MAX_REQUEST_HEAD {
+            return Ok(());
+        }
+        if socket.read_buf(&mut head).await? == 0 {
+            return Ok(());
+        }
+    }
+
+    let request_line = head.split(|&b| b == b'\n').next().unwrap_or_default();
+    let mut parts = request_line.split(|&b| b == b' ');
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    let (status, body) = match (method, path) {
+        (b"GET", b"/metrics") => ("200 OK", render(db)),
+        (b"GET", _) => ("404 Not Found", "Not Found\n".to_string()),
+        _ => ("405 Method Not Allowed", "Method Not Allowed\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let db = Db::new();
+        db.set("key".to_string(), b"value".to_vec());
+        db.stats().record_command();
+        let connected = db.stats().record_connection();
+
+        let text = render(&db);
+        assert!(text.contains("# TYPE redis_commands_processed_total counter\n"));
+        assert!(text.contains("\nredis_commands_processed_total 1\n"));
+        assert!(text.contains("\nredis_db_keys 1\n"));
+        assert!(text.contains("\nredis_connected_clients 1\n"));
+        drop(connected);
+        assert!(render(&db).contains("\nredis_connected_clients 0\n"));
+        assert!(text.ends_with('\n'));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,87 @@
+use std::sync::Mutex;
+use std::time::Instant;
+use tokio::sync::futures::Notified;
+use tokio::sync::Notify;
+
+/// Which commands CLIENT PAUSE holds back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PauseMode {
+    /// Only commands that may modify the keyspace.
+    Write,
+    All,
+}
+
+/// Server-wide CLIENT PAUSE state. Connections ask it before running each
+/// command and wait out the deadline it returns.
+#[derive(Debug, Default)]
+pub struct Pause {
+    state: Mutex<Option<(Instant, PauseMode)>>,
+    lifted: Notify,
+}
+
+impl Pause {
+    /// Pauses until `until`. An overlapping pause keeps the later deadline
+    /// and the stricter mode, as in Redis.
+    pub fn pause(&self, until: Instant, mode: PauseMode) {
+        let mut state = self.state.lock().unwrap();
+        *state = match *state {
+            Some((end, current)) if end > Instant::now() => {
+                Some((end.max(until), current.max(mode)))
+            }
+            _ => Some((until, mode)),
+        };
+    }
+
+    /// Lifts any pause at once, waking every waiting connection.
+    pub fn unpause(&self) {
+        *self.state.lock().unwrap() = None;
+        self.lifted.notify_waiters();
+    }
+
+    /// Deadline a command must wait for, if a pause currently applies to it.
+    pub fn deadline(&self, write: bool) -> Option<Instant> {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            Some((end, _)) if end <= Instant::now() => {
+                *state = None;
+                None
+            }
+            Some((end, mode)) if write || mode == PauseMode::All => Some(end),
+            _ => None,
+        }
+    }
+
+    /// Resolves on the next [`unpause`](Self::unpause). Take it before
+    /// checking [`deadline`](Self::deadline) so an unpause in between is
+    /// not missed.
+    pub fn lifted(&self) -> Notified<'_> {
+        self.lifted.notified()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pause_modes_and_merging() {
+        let pause = Pause::default();
+        assert_eq!(pause.deadline(true), None);
+
+        let soon = Instant::now() + Duration::from_secs(10);
+        pause.pause(soon, PauseMode::Write);
+        assert_eq!(pause.deadline(true), Some(soon));
+        assert_eq!(pause.deadline(false), None);
+
+        // A shorter, stricter pause keeps the deadline and takes the mode.
+        pause.pause(Instant::now() + Duration::from_secs(1), PauseMode::All);
+        assert_eq!(pause.deadline(false), Some(soon));
+
+        pause.unpause();
+        assert_eq!(pause.deadline(true), None);
+
+        pause.pause(Instant::now(), PauseMode::All);
+        assert_eq!(pause.deadline(true), None);
+    }
+} 
\ No newline at end of file
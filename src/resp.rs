@@ -1,4 +1,5 @@
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
+use std::fmt::Write;
 use thiserror::Error;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -6,141 +7,510 @@ pub enum Frame {
     Simple(String),
     Error(String),
     Integer(i64),
-    Bulk(Option<Vec<u8>>),
+    Bulk(Option<Bytes>),
     Array(Option<Vec<Frame>>),
+    /// RESP3 attribute (`|`): out-of-band key/value metadata sent ahead of
+    /// the reply it describes. It is a frame of its own here, so the reply
+    /// follows as the next frame on the stream.
+    Attribute(Vec<(Frame, Frame)>),
+    /// RESP3 big number (`(`): an integer outside the i64 range, kept as
+    /// its decimal digits.
+    BigNumber(String),
 }
 
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("incomplete frame")]
-    Incomplete,
     #[error("invalid frame format")]
     Invalid,
+    #[error("invalid bulk length")]
+    InvalidBulkLength,
+    #[error("invalid multibulk length")]
+    InvalidMultibulkLength,
+    #[error("too big inline request")]
+    InlineTooBig,
+    #[error("too big mbulk count string")]
+    MultibulkCountTooBig,
+    #[error("too big bulk count string")]
+    BulkCountTooBig,
+    #[error("nesting too deep")]
+    NestingTooDeep,
+    #[error("expected '$', got '{0}'")]
+    ExpectedBulk(char),
+}
+
+/// Bounds checked while parsing, so an oversized length prefix is refused
+/// before any of its payload is buffered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub max_bulk_len: usize,
+    /// Most elements any one array may declare.
+    pub max_array_len: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_array_len: 1_000_000,
+        }
+    }
 }
 
 impl Frame {
     pub fn encode(&self) -> Vec<u8> {
+        let mut dst = BytesMut::new();
+        self.encode_to(&mut dst);
+        dst.into()
+    }
+
+    /// Appends the wire encoding to `dst`. Nested arrays are written straight
+    /// into the same buffer, so a reply of any shape needs no temporary
+    /// per-element allocations.
+    pub fn encode_to(&self, dst: &mut BytesMut) {
+        // Writing into a BytesMut cannot fail.
         match self {
-            Frame::Simple(s) => format!("+{}\r\n", s).into_bytes(),
-            Frame::Error(msg) => format!("-{}\r\n", msg).into_bytes(),
-            Frame::Integer(num) => format!(":{}\r\n", num).into_bytes(),
-            Frame::Bulk(None) => "$-1\r\n".into(),
+            Frame::Simple(s) => write!(dst, "+{}\r\n", s).unwrap(),
+            Frame::Error(msg) => write!(dst, "-{}\r\n", msg).unwrap(),
+            Frame::Integer(num) => write!(dst, ":{}\r\n", num).unwrap(),
+            Frame::Bulk(None) => dst.extend_from_slice(b"$-1\r\n"),
             Frame::Bulk(Some(data)) => {
-                let mut res = format!("${}\r\n", data.len()).into_bytes();
-                res.extend(data);
-                res.extend(b"\r\n");
-                res
+                write!(dst, "${}\r\n", data.len()).unwrap();
+                dst.extend_from_slice(data);
+                dst.extend_from_slice(b"\r\n");
             }
-            Frame::Array(None) => "*-1\r\n".into(),
+            Frame::Array(None) => dst.extend_from_slice(b"*-1\r\n"),
             Frame::Array(Some(items)) => {
-                let mut res = format!("*{}\r\n", items.len()).into_bytes();
+                write!(dst, "*{}\r\n", items.len()).unwrap();
                 for item in items {
-                    res.extend(&item.encode());
+                    item.encode_to(dst);
+                }
+            }
+            Frame::BigNumber(digits) => write!(dst, "({}\r\n", digits).unwrap(),
+            Frame::Attribute(pairs) => {
+                write!(dst, "|{}\r\n", pairs.len()).unwrap();
+                for (key, value) in pairs {
+                    key.encode_to(dst);
+                    value.encode_to(dst);
                 }
-                res
             }
         }
     }
 
+    /// Parses one frame from the front of `src`, consuming it. Returns
+    /// `Ok(None)` and leaves `src` untouched if the frame is not complete yet.
     pub fn parse(src: &mut BytesMut) -> Result<Option<Frame>, Error> {
-        if src.is_empty() {
-            return Ok(None);
+        Self::parse_with_limits(src, Limits::default())
+    }
+
+    pub fn parse_with_limits(src: &mut BytesMut, limits: Limits) -> Result<Option<Frame>, Error> {
+        match parse_frame(src, &limits, 0)? {
+            Some((frame, len)) => {
+                src.advance(len);
+                Ok(Some(frame))
+            }
+            None => Ok(None),
         }
+    }
+}
+
+/// Accumulates the elements of an array reply, so multi-field replies
+/// read as a list of fields instead of hand-built `Frame` vectors.
+///
+/// ```
+/// use mini_redis_clone::resp::{Frame, ReplyBuilder};
+///
+/// let reply = ReplyBuilder::new()
+///     .field("keys.count", Frame::Integer(2))
+///     .simple("done")
+///     .build();
+/// assert_eq!(reply.encode(), b"*3\r\n$10\r\nkeys.count\r\n:2\r\n+done\r\n");
+/// ```
+#[derive(Debug, Default)]
+pub struct ReplyBuilder {
+    items: Vec<Frame>,
+}
 
-        match src[0] as char {
-            '+' => parse_simple(src),
-            '-' => parse_error(src),
-            ':' => parse_integer(src),
-            '$' => parse_bulk(src),
-            '*' => parse_array(src),
-            _ => Err(Error::Invalid),
+impl ReplyBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            items: Vec::with_capacity(capacity),
         }
     }
+
+    pub fn push(mut self, frame: Frame) -> Self {
+        self.items.push(frame);
+        self
+    }
+
+    pub fn extend(mut self, frames: impl IntoIterator<Item = Frame>) -> Self {
+        self.items.extend(frames);
+        self
+    }
+
+    pub fn simple(self, line: impl Into<String>) -> Self {
+        self.push(Frame::Simple(line.into()))
+    }
+
+    pub fn bulk(self, data: impl Into<Bytes>) -> Self {
+        self.push(Frame::Bulk(Some(data.into())))
+    }
+
+    pub fn integer(self, value: i64) -> Self {
+        self.push(Frame::Integer(value))
+    }
+
+    /// Appends a name/value pair the way RESP2 flattens a map: the name as a
+    /// bulk string, then the value.
+    pub fn field(self, name: &str, value: Frame) -> Self {
+        self.bulk(Bytes::copy_from_slice(name.as_bytes())).push(value)
+    }
+
+    pub fn build(self) -> Frame {
+        Frame::Array(Some(self.items))
+    }
+}
+
+/// Longest inline command line, or array or bulk header line, accepted
+/// before its line ending arrives, as in Redis.
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+/// Deepest nesting of arrays and attributes [`Frame::parse`] follows. Each
+/// level is a recursive call, so an unbounded depth could overflow the stack.
+const MAX_NESTING: usize = 128;
+
+/// Resumable request parser for a connection's read buffer.
+///
+/// [`Frame::parse`] starts over on every call, so a large or many-element
+/// frame arriving in small segments is re-scanned once per segment. A
+/// `Decoder` instead consumes the array header and completed elements as
+/// they arrive, keeping the partial array itself, and remembers how many
+/// bytes an unfinished bulk still needs so it can return early until they
+/// are buffered.
+///
+/// Requests are always arrays, so at the top level anything other than `*`
+/// starts an inline command, as typed into telnet or sent by health checks:
+/// the line is split on whitespace and returned as an array of bulk
+/// strings. As in Redis, the elements of a request array must be bulk
+/// strings; any other type, a nested array or a null bulk among them is a
+/// protocol error.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    /// The request array still collecting elements, with the number of
+    /// elements it expects.
+    array: Option<(usize, Vec<Frame>)>,
+    /// Buffered bytes needed before parsing can make progress.
+    need: usize,
 }
 
-fn parse_simple(src: &mut BytesMut) -> Result<Option<Frame>, Error> {
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total buffered bytes the next [`decode`](Self::decode) needs before it
+    /// can make progress, or 0 when that is not known yet.
+    pub fn bytes_needed(&self) -> usize {
+        self.need
+    }
+
+    /// Parses the next frame out of `src`. Unlike [`Frame::parse`], bytes of
+    /// an incomplete frame may already have been consumed when this returns
+    /// `Ok(None)`; they are held by the decoder until the frame completes.
+    pub fn decode(&mut self, src: &mut BytesMut, limits: Limits) -> Result<Option<Frame>, Error> {
+        let result = self.step(src, &limits);
+        if result.is_err() {
+            *self = Self::default();
+        }
+        result
+    }
+
+    fn step(&mut self, src: &mut BytesMut, limits: &Limits) -> Result<Option<Frame>, Error> {
+        loop {
+            if src.len() < self.need || src.is_empty() {
+                return Ok(None);
+            }
+            self.need = 0;
+
+            let Some((len, items)) = &mut self.array else {
+                if src[0] != b'*' {
+                    return self.inline(src);
+                }
+                let Some(i) = find_crlf(src) else {
+                    if src.len() > MAX_INLINE_LEN {
+                        return Err(Error::MultibulkCountTooBig);
+                    }
+                    self.need = src.len() + 1;
+                    return Ok(None);
+                };
+                let len = parse_length(&src[1..i]).ok_or(Error::Invalid)?;
+                src.advance(i + 2);
+                if len < 0 {
+                    return Ok(Some(Frame::Array(None)));
+                } else if len == 0 {
+                    return Ok(Some(Frame::Array(Some(Vec::new()))));
+                }
+                let len = len as usize;
+                if len > limits.max_array_len {
+                    return Err(Error::InvalidMultibulkLength);
+                }
+                // Cap the preallocation; the length is client-controlled.
+                self.array = Some((len, Vec::with_capacity(len.min(1024))));
+                continue;
+            };
+
+            if src[0] != b'$' {
+                return Err(Error::ExpectedBulk(src[0] as char));
+            }
+            match parse_bulk(src, limits)? {
+                Some((Frame::Bulk(None), _)) => return Err(Error::InvalidBulkLength),
+                Some((frame, used)) => {
+                    src.advance(used);
+                    items.push(frame);
+                    if items.len() == *len {
+                        let (_, items) = self.array.take().unwrap();
+                        return Ok(Some(Frame::Array(Some(items))));
+                    }
+                }
+                None => {
+                    self.need = match bulk_need(src) {
+                        Some(need) => need,
+                        // The header line itself hasn't ended yet.
+                        None if src.len() > MAX_INLINE_LEN => return Err(Error::BulkCountTooBig),
+                        None => src.len() + 1,
+                    };
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Parses one inline command line into an array of bulk strings.
+    fn inline(&mut self, src: &mut BytesMut) -> Result<Option<Frame>, Error> {
+        let Some(end) = memchr::memchr(b'\n', src) else {
+            if src.len() > MAX_INLINE_LEN {
+                return Err(Error::InlineTooBig);
+            }
+            self.need = src.len() + 1;
+            return Ok(None);
+        };
+        let line = src.split_to(end + 1);
+        // A bare LF ends the line too, for clients that don't send CR.
+        let line = line[..end].strip_suffix(b"\r").unwrap_or(&line[..end]);
+        let args = line
+            .split(u8::is_ascii_whitespace)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| Frame::Bulk(Some(Bytes::copy_from_slice(arg))))
+            .collect();
+        Ok(Some(Frame::Array(Some(args))))
+    }
+}
+
+/// Total bytes an incomplete bulk at the front of `src` occupies, once its
+/// header has arrived.
+fn bulk_need(src: &[u8]) -> Option<usize> {
+    if src.first() != Some(&b'$') {
+        return None;
+    }
+    let i = find_crlf(src)?;
+    let len = parse_length(&src[1..i])?;
+    Some(i + 2 + usize::try_from(len).ok()? + 2)
+}
+
+/// A parsed frame together with the number of bytes it occupied.
+type Parsed = Option<(Frame, usize)>;
+
+/// Parses the frame at the front of `src`, which sits `depth` arrays or
+/// attributes deep.
+fn parse_frame(src: &[u8], limits: &Limits, depth: usize) -> Result<Parsed, Error> {
+    if src.is_empty() {
+        return Ok(None);
+    }
+    if depth > MAX_NESTING {
+        return Err(Error::NestingTooDeep);
+    }
+
+    match src[0] as char {
+        '+' => parse_simple(src),
+        '-' => parse_error(src),
+        ':' => parse_integer(src),
+        '$' => parse_bulk(src, limits),
+        '*' => parse_array(src, limits, depth),
+        '|' => parse_attribute(src, limits, depth),
+        '(' => parse_big_number(src),
+        _ => Err(Error::Invalid),
+    }
+}
+
+fn parse_simple(src: &[u8]) -> Result<Parsed, Error> {
     if let Some(i) = find_crlf(src) {
         let line = String::from_utf8_lossy(&src[1..i]).to_string();
-        src.advance(i + 2);
-        Ok(Some(Frame::Simple(line)))
+        Ok(Some((Frame::Simple(line), i + 2)))
     } else {
         Ok(None)
     }
 }
 
-fn parse_error(src: &mut BytesMut) -> Result<Option<Frame>, Error> {
+fn parse_error(src: &[u8]) -> Result<Parsed, Error> {
     if let Some(i) = find_crlf(src) {
         let line = String::from_utf8_lossy(&src[1..i]).to_string();
-        src.advance(i + 2);
-        Ok(Some(Frame::Error(line)))
+        Ok(Some((Frame::Error(line), i + 2)))
     } else {
         Ok(None)
     }
 }
 
-fn parse_integer(src: &mut BytesMut) -> Result<Option<Frame>, Error> {
+fn parse_integer(src: &[u8]) -> Result<Parsed, Error> {
     if let Some(i) = find_crlf(src) {
         let line = &src[1..i];
-        let num = atoi::atoi::<i64>(line).ok_or(Error::Invalid)?;
-        src.advance(i + 2);
-        Ok(Some(Frame::Integer(num)))
+        let num = parse_decimal(line).ok_or(Error::Invalid)?;
+        Ok(Some((Frame::Integer(num), i + 2)))
     } else {
         Ok(None)
     }
 }
 
-fn parse_bulk(src: &mut BytesMut) -> Result<Option<Frame>, Error> {
+fn parse_bulk(src: &[u8], limits: &Limits) -> Result<Parsed, Error> {
     if let Some(i) = find_crlf(src) {
-        let len = atoi::atoi::<i64>(&src[1..i]).ok_or(Error::Invalid)?;
+        let len = parse_length(&src[1..i]).ok_or(Error::Invalid)?;
         
         if len < 0 {
-            src.advance(i + 2);
-            return Ok(Some(Frame::Bulk(None)));
+            return Ok(Some((Frame::Bulk(None), i + 2)));
         }
 
         let len = len as usize;
+        if len > limits.max_bulk_len {
+            return Err(Error::InvalidBulkLength);
+        }
         let end = i + 2 + len + 2;
 
         if src.len() < end {
             return Ok(None);
         }
 
-        let data = src[i + 2..i + 2 + len].to_vec();
-        src.advance(end);
-        Ok(Some(Frame::Bulk(Some(data))))
+        if &src[end - 2..end] != b"\r\n" {
+            return Err(Error::Invalid);
+        }
+
+        let data = Bytes::copy_from_slice(&src[i + 2..i + 2 + len]);
+        Ok(Some((Frame::Bulk(Some(data)), end)))
     } else {
         Ok(None)
     }
 }
 
-fn parse_array(src: &mut BytesMut) -> Result<Option<Frame>, Error> {
+fn parse_array(src: &[u8], limits: &Limits, depth: usize) -> Result<Parsed, Error> {
     if let Some(i) = find_crlf(src) {
-        let len = atoi::atoi::<i64>(&src[1..i]).ok_or(Error::Invalid)?;
+        let len = parse_length(&src[1..i]).ok_or(Error::Invalid)?;
 
         if len < 0 {
-            src.advance(i + 2);
-            return Ok(Some(Frame::Array(None)));
+            return Ok(Some((Frame::Array(None), i + 2)));
         }
 
         let len = len as usize;
-        src.advance(i + 2);
+        if len > limits.max_array_len {
+            return Err(Error::InvalidMultibulkLength);
+        }
+        let mut pos = i + 2;
 
-        let mut items = Vec::with_capacity(len);
+        // Cap the preallocation, as the decoder does; the length is
+        // client-controlled.
+        let mut items = Vec::with_capacity(len.min(1024));
         for _ in 0..len {
-            match Frame::parse(src)? {
-                Some(frame) => items.push(frame),
+            match parse_frame(&src[pos..], limits, depth + 1)? {
+                Some((frame, used)) => {
+                    items.push(frame);
+                    pos += used;
+                }
                 None => return Ok(None),
             }
         }
 
-        Ok(Some(Frame::Array(Some(items))))
+        Ok(Some((Frame::Array(Some(items)), pos)))
     } else {
         Ok(None)
     }
 }
 
+fn parse_big_number(src: &[u8]) -> Result<Parsed, Error> {
+    let Some(i) = find_crlf(src) else {
+        return Ok(None);
+    };
+    let line = &src[1..i];
+    let digits = line.strip_prefix(b"-").unwrap_or(line);
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return Err(Error::Invalid);
+    }
+    // All ASCII, so this never replaces anything.
+    let number = String::from_utf8_lossy(line).into_owned();
+    Ok(Some((Frame::BigNumber(number), i + 2)))
+}
+
+fn parse_attribute(src: &[u8], limits: &Limits, depth: usize) -> Result<Parsed, Error> {
+    let Some(i) = find_crlf(src) else {
+        return Ok(None);
+    };
+    // Unlike arrays, an attribute has no null form.
+    let len = match parse_length(&src[1..i]) {
+        Some(len) if len >= 0 => len as usize,
+        _ => return Err(Error::Invalid),
+    };
+    let mut pos = i + 2;
+
+    let mut pairs = Vec::with_capacity(len.min(1024));
+    for _ in 0..len {
+        let Some((key, used)) = parse_frame(&src[pos..], limits, depth + 1)? else {
+            return Ok(None);
+        };
+        pos += used;
+        let Some((value, used)) = parse_frame(&src[pos..], limits, depth + 1)? else {
+            return Ok(None);
+        };
+        pos += used;
+        pairs.push((key, value));
+    }
+
+    Ok(Some((Frame::Attribute(pairs), pos)))
+}
+
+/// Strictly parses a RESP integer payload: an optional `-` followed by one or
+/// more ASCII digits, with no sign `+`, whitespace or trailing garbage. As
+/// with Redis's `string2ll`, leading zeros and `-0` are refused too.
+pub(crate) fn parse_decimal(line: &[u8]) -> Option<i64> {
+    if line == b"0" {
+        return Some(0);
+    }
+    let digits = line.strip_prefix(b"-").unwrap_or(line);
+    if !matches!(digits, [b'1'..=b'9', ..]) || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(line).ok()?.parse().ok()
+}
+
+/// Strictly parses the length of a bulk string or array header: `-1` for
+/// null, otherwise a plain non-negative decimal without sign, leading zeros
+/// or whitespace.
+fn parse_length(line: &[u8]) -> Option<i64> {
+    if line == b"-1" {
+        return Some(-1);
+    }
+    match line {
+        [b'0'] => Some(0),
+        [b'1'..=b'9', ..] => parse_decimal(line),
+        _ => None,
+    }
+}
+
+/// Returns the index of the `\r` in the first `\r\n` of `src`.
 fn find_crlf(src: &[u8]) -> Option<usize> {
-    src.windows(2).position(|bytes| bytes == b"\r\n")
+    // memchr jumps straight between newlines; a `\n` not preceded by `\r` is
+    // part of the payload, so keep looking past it.
+    memchr::memchr_iter(b'\n', src)
+        .find(|&i| i > 0 && src[i - 1] == b'\r')
+        .map(|i| i - 1)
 }
 
 #[cfg(test)]
@@ -173,7 +543,7 @@ mod tests {
     fn test_parse_bulk() {
         let mut bytes = BytesMut::from("$5\r\nhello\r\n");
         let frame = Frame::parse(&mut bytes).unwrap().unwrap();
-        assert_eq!(frame, Frame::Bulk(Some(b"hello".to_vec())));
+        assert_eq!(frame, Frame::Bulk(Some(Bytes::from_static(b"hello"))));
     }
 
     #[test]
@@ -182,4 +552,509 @@ mod tests {
         let frame = Frame::parse(&mut bytes).unwrap().unwrap();
         assert_eq!(frame, Frame::Bulk(None));
     }
+
+    #[test]
+    fn test_empty_bulk_round_trip() {
+        let mut bytes = BytesMut::from("$0\r\n\r\n");
+        let frame = Frame::parse(&mut bytes).unwrap().unwrap();
+        assert_eq!(frame, Frame::Bulk(Some(Bytes::new())));
+        assert!(bytes.is_empty());
+        assert_eq!(frame.encode(), b"$0\r\n\r\n");
+    }
+
+    #[test]
+    fn test_parse_bulk_missing_terminator() {
+        let mut bytes = BytesMut::from("$0\r\nab");
+        assert!(matches!(Frame::parse(&mut bytes), Err(Error::Invalid)));
+    }
+
+    #[test]
+    fn test_parse_integer_strict() {
+        for input in [":\r\n", ": 5\r\n", ":5 \r\n", ":+5\r\n", ":-\r\n", ":12a\r\n"] {
+            let mut bytes = BytesMut::from(input);
+            assert!(
+                matches!(Frame::parse(&mut bytes), Err(Error::Invalid)),
+                "{:?}",
+                input
+            );
+        }
+
+        let mut bytes = BytesMut::from(":-9223372036854775808\r\n");
+        let frame = Frame::parse(&mut bytes).unwrap().unwrap();
+        assert_eq!(frame, Frame::Integer(i64::MIN));
+
+        let mut bytes = BytesMut::from(":9223372036854775808\r\n");
+        assert!(matches!(Frame::parse(&mut bytes), Err(Error::Invalid)));
+    }
+
+    #[test]
+    fn test_partial_array_is_not_consumed() {
+        let full = b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\n";
+        let mut bytes = BytesMut::from(&full[..full.len() - 4]);
+        assert_eq!(Frame::parse(&mut bytes).unwrap(), None);
+        assert_eq!(bytes.len(), full.len() - 4);
+
+        bytes.extend_from_slice(&full[full.len() - 4..]);
+        let frame = Frame::parse(&mut bytes).unwrap().unwrap();
+        assert_eq!(
+            frame,
+            Frame::Array(Some(vec![
+                Frame::Bulk(Some(Bytes::from_static(b"GET"))),
+                Frame::Bulk(Some(Bytes::from_static(b"hello"))),
+            ]))
+        );
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_large_array_round_trip() {
+        let items: Vec<Frame> = (0..10_000)
+            .map(|i| Frame::Bulk(Some(Bytes::from(format!("element-{}", i)))))
+            .collect();
+        let frame = Frame::Array(Some(items));
+
+        let mut bytes = BytesMut::new();
+        frame.encode_to(&mut bytes);
+        assert_eq!(&bytes[..], &frame.encode()[..]);
+        assert_eq!(Frame::parse(&mut bytes).unwrap(), Some(frame));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_nested_array_round_trip() {
+        // XRANGE-shaped: [[id, [field, value, ...]], ...]
+        let entry = |id: &str| {
+            Frame::Array(Some(vec![
+                Frame::Bulk(Some(Bytes::copy_from_slice(id.as_bytes()))),
+                Frame::Array(Some(vec![
+                    Frame::Bulk(Some(Bytes::from_static(b"field"))),
+                    Frame::Bulk(Some(Bytes::from_static(b"value"))),
+                    Frame::Array(Some(vec![Frame::Integer(-1), Frame::Bulk(None)])),
+                ])),
+            ]))
+        };
+        let frame = Frame::Array(Some(vec![entry("1-0"), entry("2-0"), Frame::Array(None)]));
+
+        let mut bytes = BytesMut::new();
+        frame.encode_to(&mut bytes);
+        assert_eq!(
+            &bytes[..],
+            &b"*3\r\n*2\r\n$3\r\n1-0\r\n*3\r\n$5\r\nfield\r\n$5\r\nvalue\r\n*2\r\n:-1\r\n$-1\r\n\
+               *2\r\n$3\r\n2-0\r\n*3\r\n$5\r\nfield\r\n$5\r\nvalue\r\n*2\r\n:-1\r\n$-1\r\n*-1\r\n"[..]
+        );
+        assert_eq!(Frame::parse(&mut bytes).unwrap(), Some(frame));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_max_bulk_len() {
+        let limits = Limits {
+            max_bulk_len: 4,
+            ..Limits::default()
+        };
+
+        let mut bytes = BytesMut::from("$4\r\nabcd\r\n");
+        let frame = Frame::parse_with_limits(&mut bytes, limits).unwrap().unwrap();
+        assert_eq!(frame, Frame::Bulk(Some(Bytes::from_static(b"abcd"))));
+
+        // Rejected from the header alone, before the payload arrives.
+        let mut bytes = BytesMut::from("*1\r\n$5\r\n");
+        assert!(matches!(
+            Frame::parse_with_limits(&mut bytes, limits),
+            Err(Error::InvalidBulkLength)
+        ));
+    }
+
+    #[test]
+    fn test_find_crlf_edge_cases() {
+        assert_eq!(find_crlf(b""), None);
+        assert_eq!(find_crlf(b"\n"), None);
+        assert_eq!(find_crlf(b"\r"), None);
+        assert_eq!(find_crlf(b"\n\r"), None);
+        assert_eq!(find_crlf(b"\r\n"), Some(0));
+        assert_eq!(find_crlf(b"ab\ncd\r\n"), Some(5));
+        assert_eq!(find_crlf(b"\r\r\n"), Some(1));
+        assert_eq!(find_crlf(b"a\r\nb\r\n"), Some(1));
+    }
+
+    /// Compares against the old `windows(2)` scan; run with
+    /// `cargo test --release -- --ignored --nocapture bench_find_crlf`.
+    #[test]
+    #[ignore]
+    fn bench_find_crlf() {
+        use std::time::Instant;
+
+        let mut buf = vec![b'x'; 1024 * 1024];
+        buf.extend_from_slice(b"\r\n");
+        let windows = |src: &[u8]| src.windows(2).position(|bytes| bytes == b"\r\n");
+        const ROUNDS: u32 = 100;
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            assert_eq!(windows(std::hint::black_box(&buf)), Some(1024 * 1024));
+        }
+        let old = start.elapsed() / ROUNDS;
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            assert_eq!(find_crlf(std::hint::black_box(&buf)), Some(1024 * 1024));
+        }
+        let new = start.elapsed() / ROUNDS;
+
+        println!("1MB find_crlf: windows {:?}, memchr {:?}", old, new);
+    }
+
+    /// Compares against concatenating each element's own encoding, which is
+    /// what array replies did before `encode_to`; run with
+    /// `cargo test --release -- --ignored --nocapture bench_array_reply`.
+    #[test]
+    #[ignore]
+    fn bench_array_reply() {
+        use std::time::Instant;
+
+        fn concat(frame: &Frame) -> Vec<u8> {
+            match frame {
+                Frame::Array(Some(items)) => {
+                    let mut out = format!("*{}\r\n", items.len()).into_bytes();
+                    for item in items {
+                        out.extend_from_slice(&concat(item));
+                    }
+                    out
+                }
+                other => other.encode(),
+            }
+        }
+
+        let elements = (0..10_000)
+            .map(|i| Frame::Bulk(Some(Bytes::from(format!("element:{}", i)))))
+            .collect();
+        let frame = Frame::Array(Some(elements));
+        const ROUNDS: u32 = 100;
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            std::hint::black_box(concat(std::hint::black_box(&frame)));
+        }
+        let old = start.elapsed() / ROUNDS;
+
+        let mut dst = BytesMut::new();
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            dst.clear();
+            std::hint::black_box(&frame).encode_to(&mut dst);
+            std::hint::black_box(&dst);
+        }
+        let new = start.elapsed() / ROUNDS;
+
+        assert_eq!(&dst[..], &concat(&frame)[..]);
+        println!("10k-element array reply: concat {:?}, encode_to {:?}", old, new);
+    }
+
+    #[test]
+    fn test_decoder_byte_at_a_time() {
+        let mut elements = Vec::new();
+        for i in 0..20_000 {
+            elements.push(Frame::Bulk(Some(Bytes::from(format!("{}", i)))));
+        }
+        elements.push(Frame::Bulk(Some(Bytes::new())));
+        elements.push(Frame::Bulk(Some(Bytes::from(vec![b'v'; 1024 * 1024]))));
+        let frame = Frame::Array(Some(elements));
+        let wire = frame.encode();
+
+        // Re-parsing from the start on every byte would take quadratic time
+        // here; the decoder resumes where it stopped.
+        let mut decoder = Decoder::new();
+        let mut bytes = BytesMut::new();
+        let mut parsed = Vec::new();
+        for &byte in &wire {
+            bytes.extend_from_slice(&[byte]);
+            if let Some(frame) = decoder.decode(&mut bytes, Limits::default()).unwrap() {
+                parsed.push(frame);
+            }
+        }
+        assert_eq!(parsed, vec![frame]);
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_decoder_pipelined_and_reset_on_error() {
+        let mut decoder = Decoder::new();
+        let mut bytes = BytesMut::from("*1\r\n$4\r\nPING\r\n*1\r\n$1\r\na\r\n*2\r\n$1\r\n");
+        let limits = Limits::default();
+        let bulks = |args: &[&str]| {
+            Some(Frame::Array(Some(
+                args.iter()
+                    .map(|arg| Frame::Bulk(Some(Bytes::copy_from_slice(arg.as_bytes()))))
+                    .collect(),
+            )))
+        };
+        assert_eq!(decoder.decode(&mut bytes, limits).unwrap(), bulks(&["PING"]));
+        assert_eq!(decoder.decode(&mut bytes, limits).unwrap(), bulks(&["a"]));
+        assert_eq!(decoder.decode(&mut bytes, limits).unwrap(), None);
+
+        bytes.extend_from_slice(b"b\r\n!bad\r\n");
+        assert!(matches!(decoder.decode(&mut bytes, limits), Err(Error::ExpectedBulk('!'))));
+        let mut bytes = BytesMut::from("*x\r\n");
+        assert!(matches!(decoder.decode(&mut bytes, limits), Err(Error::Invalid)));
+        let mut bytes = BytesMut::from("*1\r\n$1\r\n2\r\n");
+        assert_eq!(decoder.decode(&mut bytes, limits).unwrap(), bulks(&["2"]));
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(parse_decimal(b"0"), Some(0));
+        assert_eq!(parse_decimal(b"7"), Some(7));
+        assert_eq!(parse_decimal(b"-10"), Some(-10));
+        assert_eq!(parse_decimal(b"-9223372036854775808"), Some(i64::MIN));
+        for input in [
+            &b"007"[..],
+            b"-07",
+            b"-0",
+            b"+5",
+            b"",
+            b"-",
+            b" 1",
+            b"1 ",
+            b"9223372036854775808",
+        ] {
+            assert_eq!(parse_decimal(input), None, "{:?}", String::from_utf8_lossy(input));
+        }
+    }
+
+    #[test]
+    fn test_malformed_lengths() {
+        for input in [
+            &b"$+5\r\nhello\r\n"[..],
+            b"$ 5\r\nhello\r\n",
+            b"$05\r\nhello\r\n",
+            b"$-2\r\n",
+            b"$\r\n",
+            b"*abc\r\n",
+            b"*+1\r\n:1\r\n",
+            b"*01\r\n:1\r\n",
+            b"*-5\r\n",
+        ] {
+            let mut bytes = BytesMut::from(input);
+            assert!(
+                matches!(Frame::parse(&mut bytes), Err(Error::Invalid)),
+                "{:?}",
+                String::from_utf8_lossy(input)
+            );
+            // Outside an array the decoder would take a bulk as inline.
+            let request = match input[0] {
+                b'$' => [b"*1\r\n", input].concat(),
+                _ => input.to_vec(),
+            };
+            let mut bytes = BytesMut::from(&request[..]);
+            assert!(matches!(
+                Decoder::new().decode(&mut bytes, Limits::default()),
+                Err(Error::Invalid)
+            ));
+        }
+
+        let mut bytes = BytesMut::from(&b"*2\r\n$0\r\n\r\n$-1\r\n"[..]);
+        assert_eq!(
+            Frame::parse(&mut bytes).unwrap(),
+            Some(Frame::Array(Some(vec![Frame::Bulk(Some(Bytes::new())), Frame::Bulk(None)])))
+        );
+
+        // A request's arguments can't be null.
+        let mut bytes = BytesMut::from(&b"*2\r\n$0\r\n\r\n$-1\r\n"[..]);
+        assert!(matches!(
+            Decoder::new().decode(&mut bytes, Limits::default()),
+            Err(Error::InvalidBulkLength)
+        ));
+    }
+
+    #[test]
+    fn test_attribute_round_trip() {
+        let attribute = Frame::Attribute(vec![(
+            Frame::Simple("key-popularity".to_string()),
+            Frame::Array(Some(vec![
+                Frame::Bulk(Some(Bytes::from_static(b"a"))),
+                Frame::Integer(7),
+            ])),
+        )]);
+        let reply = Frame::Array(Some(vec![Frame::Integer(2039), Frame::Integer(9543)]));
+
+        let mut bytes = BytesMut::new();
+        attribute.encode_to(&mut bytes);
+        reply.encode_to(&mut bytes);
+        assert_eq!(
+            &bytes[..],
+            &b"|1\r\n+key-popularity\r\n*2\r\n$1\r\na\r\n:7\r\n*2\r\n:2039\r\n:9543\r\n"[..]
+        );
+
+        let mut partial = bytes.split_to(10);
+        assert_eq!(Frame::parse(&mut partial).unwrap(), None);
+        partial.unsplit(bytes);
+        let mut bytes = partial;
+        assert_eq!(Frame::parse(&mut bytes).unwrap(), Some(attribute.clone()));
+        assert_eq!(Frame::parse(&mut bytes).unwrap(), Some(reply));
+        assert!(bytes.is_empty());
+
+        // Clients can't send attributes: at the top of a request they are
+        // just the start of an inline command.
+        let mut bytes = BytesMut::from(&b"|0\r\n"[..]);
+        assert_eq!(
+            Decoder::new().decode(&mut bytes, Limits::default()).unwrap(),
+            Some(Frame::Array(Some(vec![Frame::Bulk(Some(Bytes::from_static(b"|0")))])))
+        );
+
+        let mut bytes = BytesMut::from(&b"|0\r\n"[..]);
+        assert_eq!(Frame::parse(&mut bytes).unwrap(), Some(Frame::Attribute(Vec::new())));
+        let mut bytes = BytesMut::from(&b"|-1\r\n"[..]);
+        assert!(matches!(Frame::parse(&mut bytes), Err(Error::Invalid)));
+    }
+
+    #[test]
+    fn test_reply_builder_matches_manual_frames() {
+        let manual = Frame::Array(Some(vec![
+            Frame::Bulk(Some(Bytes::from_static(b"peak.allocated"))),
+            Frame::Integer(5),
+            Frame::Bulk(Some(Bytes::from_static(b"maxmemory"))),
+            Frame::Bulk(Some(Bytes::from_static(b"0"))),
+            Frame::Simple("HELP".to_string()),
+            Frame::Array(Some(vec![Frame::Integer(1)])),
+        ]));
+        let built = ReplyBuilder::new()
+            .field("peak.allocated", Frame::Integer(5))
+            .field("maxmemory", Frame::Bulk(Some(Bytes::from_static(b"0"))))
+            .simple("HELP")
+            .push(ReplyBuilder::with_capacity(1).integer(1).build())
+            .build();
+        assert_eq!(built, manual);
+        assert_eq!(built.encode(), manual.encode());
+
+        assert_eq!(ReplyBuilder::new().build().encode(), b"*0\r\n");
+    }
+
+    #[test]
+    fn test_max_array_len() {
+        let limits = Limits {
+            max_array_len: 2,
+            ..Limits::default()
+        };
+
+        let mut bytes = BytesMut::from("*2\r\n:1\r\n:2\r\n");
+        assert!(Frame::parse_with_limits(&mut bytes, limits).unwrap().is_some());
+
+        // Refused from the header alone, before any element arrives.
+        for input in ["*3\r\n", "*1\r\n*3\r\n"] {
+            let mut bytes = BytesMut::from(input);
+            assert!(matches!(
+                Frame::parse_with_limits(&mut bytes, limits),
+                Err(Error::InvalidMultibulkLength)
+            ));
+        }
+        let mut bytes = BytesMut::from("*3\r\n");
+        assert!(matches!(
+            Decoder::new().decode(&mut bytes, limits),
+            Err(Error::InvalidMultibulkLength)
+        ));
+    }
+
+    #[test]
+    fn test_decoder_inline_commands() {
+        let limits = Limits::default();
+        let bulks = |args: &[&str]| {
+            Some(Frame::Array(Some(
+                args.iter()
+                    .map(|arg| Frame::Bulk(Some(Bytes::copy_from_slice(arg.as_bytes()))))
+                    .collect(),
+            )))
+        };
+
+        let mut decoder = Decoder::new();
+        let mut bytes = BytesMut::from("PING\r\nset  k\tv\n\r\n*1\r\n$4\r\nPING\r\nGET");
+        assert_eq!(decoder.decode(&mut bytes, limits).unwrap(), bulks(&["PING"]));
+        assert_eq!(decoder.decode(&mut bytes, limits).unwrap(), bulks(&["set", "k", "v"]));
+        assert_eq!(decoder.decode(&mut bytes, limits).unwrap(), bulks(&[]));
+        assert_eq!(decoder.decode(&mut bytes, limits).unwrap(), bulks(&["PING"]));
+        assert_eq!(decoder.decode(&mut bytes, limits).unwrap(), None);
+        bytes.extend_from_slice(b" k\r\n");
+        assert_eq!(decoder.decode(&mut bytes, limits).unwrap(), bulks(&["GET", "k"]));
+
+        let mut bytes = BytesMut::from(&vec![b'a'; MAX_INLINE_LEN + 1][..]);
+        assert!(matches!(decoder.decode(&mut bytes, limits), Err(Error::InlineTooBig)));
+
+        // Inside an array, an element must still be a bulk string.
+        let mut bytes = BytesMut::from("*1\r\nPING\r\n");
+        assert!(matches!(decoder.decode(&mut bytes, limits), Err(Error::ExpectedBulk('P'))));
+    }
+
+    #[test]
+    fn test_big_number_round_trip() {
+        let number = "-1234567890123456789012345678901234567890";
+        let frame = Frame::BigNumber(number.to_string());
+        let encoded = frame.encode();
+        assert_eq!(encoded, format!("({}\r\n", number).into_bytes());
+
+        let mut bytes = BytesMut::from(&encoded[..]);
+        assert_eq!(Frame::parse(&mut bytes).unwrap(), Some(frame));
+
+        for bad in ["(\r\n", "(-\r\n", "(12a\r\n", "(+1\r\n"] {
+            let mut bytes = BytesMut::from(bad);
+            assert!(matches!(Frame::parse(&mut bytes), Err(Error::Invalid)), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        // Without a limit this recursed once per level and overflowed the
+        // stack.
+        let mut wire = b"*1\r\n".to_vec();
+        wire.extend(b"|1\r\n".repeat(200_000));
+        let mut bytes = BytesMut::from(&wire[..]);
+        assert!(matches!(Frame::parse(&mut bytes), Err(Error::NestingTooDeep)));
+
+        let mut wire = b"*1\r\n".repeat(MAX_NESTING);
+        wire.extend(b":1\r\n");
+        let mut bytes = BytesMut::from(&wire[..]);
+        assert!(Frame::parse(&mut bytes).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_decoder_requires_bulk_elements() {
+        let limits = Limits::default();
+        for (input, got) in [
+            (&b"*2\r\n$1\r\na\r\n:1\r\n"[..], ':'),
+            (b"*1\r\n+OK\r\n", '+'),
+            (b"*1\r\n|1\r\n+a\r\n+b\r\n", '|'),
+            (b"*1\r\n*1\r\n$1\r\na\r\n", '*'),
+        ] {
+            let mut bytes = BytesMut::from(input);
+            match Decoder::new().decode(&mut bytes, limits) {
+                Err(Error::ExpectedBulk(c)) => assert_eq!(c, got),
+                other => panic!("{:?}: {:?}", String::from_utf8_lossy(input), other),
+            }
+        }
+
+        // Deeply nested arrays are refused at the second header instead of
+        // being stacked up.
+        let mut bytes = BytesMut::from(&b"*1\r\n".repeat(200_000)[..]);
+        assert!(matches!(
+            Decoder::new().decode(&mut bytes, limits),
+            Err(Error::ExpectedBulk('*'))
+        ));
+        assert_eq!(Error::ExpectedBulk('*').to_string(), "expected '$', got '*'");
+    }
+
+    #[test]
+    fn test_header_line_limit() {
+        let limits = Limits::default();
+        let mut decoder = Decoder::new();
+
+        // A header up to the limit is still waited on.
+        let mut bytes = BytesMut::from(&b"*"[..]);
+        bytes.extend_from_slice(&b"1".repeat(MAX_INLINE_LEN - 1));
+        assert_eq!(decoder.decode(&mut bytes, limits).unwrap(), None);
+        bytes.extend_from_slice(b"1");
+        assert!(matches!(decoder.decode(&mut bytes, limits), Err(Error::MultibulkCountTooBig)));
+
+        let mut bytes = BytesMut::from(&b"*1\r\n$"[..]);
+        bytes.extend_from_slice(&b"1".repeat(MAX_INLINE_LEN));
+        assert!(matches!(decoder.decode(&mut bytes, limits), Err(Error::BulkCountTooBig)));
+        assert_eq!(Error::BulkCountTooBig.to_string(), "too big bulk count string");
+    }
 } 
\ No newline at end of file
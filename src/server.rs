@@ -0,0 +1,1314 @@
+use bytes::BytesMut;
+use std::fmt;
+use std::io::{self, IoSlice};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use log::Level;
+use tokio::sync::{broadcast, mpsc};
+
+use crate::client::Client;
+use crate::command::Command;
+use crate::config::{Config, LogLevel, MaxMemoryPolicy};
+use crate::db::Db;
+use crate::metrics;
+use crate::resp::{Decoder, Frame};
+
+/// Logs through the `log` facade, but only if `level` passes the server's
+/// current `loglevel` setting. The check doesn't take the config lock.
+macro_rules! server_log {
+    ($db:expr, $level:expr, $($arg:tt)+) => {
+        if $db.log_enabled($level) {
+            log::log!($level, $($arg)+);
+        }
+    };
+}
+
+/// Per-connection context prefixed to every log line about that client.
+#[derive(Debug, Clone, Copy)]
+struct ConnectionContext {
+    id: u64,
+    addr: SocketAddr,
+}
+
+impl fmt::Display for ConnectionContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "conn={} addr={}", self.id, self.addr)
+    }
+}
+
+/// Configures a [`Server`] before binding it.
+///
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// let server = mini_redis_clone::Server::builder()
+///     .port(6379)
+///     .maxmemory(64 * 1024 * 1024)
+///     .password("secret")
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServerBuilder {
+    bind: Vec<IpAddr>,
+    ports: Vec<u16>,
+    config: Config,
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        Self {
+            bind: vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            ports: vec![6379],
+            config: Config::default(),
+        }
+    }
+}
+
+impl ServerBuilder {
+    /// Addresses to listen on; each is bound on every configured port.
+    pub fn bind(mut self, addrs: impl IntoIterator<Item = IpAddr>) -> Self {
+        self.bind = addrs.into_iter().collect();
+        self
+    }
+
+    /// Port to listen on; 0 picks a free one, see [`Server::local_addrs`].
+    pub fn port(mut self, port: u16) -> Self {
+        self.ports = vec![port];
+        self
+    }
+
+    /// Listens on several ports at once, all serving the same keyspace.
+    pub fn ports(mut self, ports: impl IntoIterator<Item = u16>) -> Self {
+        self.ports = ports.into_iter().collect();
+        self
+    }
+
+    pub fn maxmemory(mut self, bytes: usize) -> Self {
+        self.config.maxmemory = bytes;
+        self
+    }
+
+    pub fn maxmemory_policy(mut self, policy: MaxMemoryPolicy) -> Self {
+        self.config.maxmemory_policy = policy;
+        self
+    }
+
+    pub fn loglevel(mut self, level: LogLevel) -> Self {
+        self.config.loglevel = level;
+        self
+    }
+
+    /// Number of keyspace shards; see [`Config::db_shards`].
+    pub fn db_shards(mut self, shards: usize) -> Self {
+        self.config.db_shards = shards;
+        self
+    }
+
+    /// Allows the DEBUG command, which is refused by default.
+    pub fn enable_debug_command(mut self, enabled: bool) -> Self {
+        self.config.enable_debug_command = enabled;
+        self
+    }
+
+    /// Listen backlog for every listener; see [`Config::tcp_backlog`].
+    pub fn tcp_backlog(mut self, backlog: u32) -> Self {
+        self.config.tcp_backlog = backlog;
+        self
+    }
+
+    /// Serves Prometheus metrics over HTTP on `port` at every bind address;
+    /// see [`Config::metrics_port`].
+    pub fn metrics_port(mut self, port: u16) -> Self {
+        self.config.metrics_port = port;
+        self
+    }
+
+    /// Requires clients to AUTH with `password` before any other command.
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.config.requirepass = Some(password.into());
+        self
+    }
+
+    /// Binds every listener, so address errors surface here rather than in
+    /// [`Server::run`].
+    pub async fn build(self) -> io::Result<Server> {
+        let mut listeners = Vec::with_capacity(self.bind.len() * self.ports.len());
+        for &ip in &self.bind {
+            for &port in &self.ports {
+                listeners.push(listen(SocketAddr::new(ip, port), self.config.tcp_backlog)?);
+            }
+        }
+        let mut metrics_listeners = Vec::new();
+        if self.config.metrics_port != 0 {
+            for &ip in &self.bind {
+                let addr = SocketAddr::new(ip, self.config.metrics_port);
+                metrics_listeners.push(listen(addr, self.config.tcp_backlog)?);
+            }
+        }
+        Ok(Server {
+            listeners,
+            metrics_listeners,
+            db: Arc::new(Db::with_config(self.config)),
+        })
+    }
+}
+
+/// Binds a listener by hand rather than with `TcpListener::bind`, whose
+/// backlog is fixed, so `tcp-backlog` reaches `listen`.
+fn listen(addr: SocketAddr, backlog: u32) -> io::Result<TcpListener> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    // Matches TcpListener::bind, so a restart can reuse a port in TIME_WAIT.
+    #[cfg(unix)]
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+    socket.listen(backlog)
+}
+
+/// A bound server, ready to accept clients with [`Server::run`].
+pub struct Server {
+    listeners: Vec<TcpListener>,
+    metrics_listeners: Vec<TcpListener>,
+    db: Arc<Db>,
+}
+
+impl Server {
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::default()
+    }
+
+    /// Addresses actually bound, in bind-address then port order.
+    pub fn local_addrs(&self) -> io::Result<Vec<SocketAddr>> {
+        self.listeners.iter().map(TcpListener::local_addr).collect()
+    }
+
+    /// Addresses the metrics listener is bound to; empty unless
+    /// [`ServerBuilder::metrics_port`] was set.
+    pub fn metrics_addrs(&self) -> io::Result<Vec<SocketAddr>> {
+        self.metrics_listeners.iter().map(TcpListener::local_addr).collect()
+    }
+
+    /// Accepts and serves clients until `shutdown` fires, or forever if no
+    /// receiver is given.
+    pub async fn run(self, shutdown: Option<broadcast::Receiver<()>>) -> Result<(), Box<dyn std::error::Error>> {
+        let Server { listeners, metrics_listeners, db } = self;
+
+        // Every listener feeds accepted sockets into one channel, so the
+        // connection setup below is shared no matter which address was used.
+        let (accept_tx, mut accept_rx) = mpsc::channel(listeners.len().max(1));
+        let mut acceptors = Vec::with_capacity(listeners.len());
+        for listener in listeners {
+            server_log!(db, Level::Info, "Server listening on {}", listener.local_addr()?);
+            let accept_tx = accept_tx.clone();
+            acceptors.push(tokio::spawn(async move {
+                loop {
+                    let accepted = listener.accept().await;
+                    if accept_tx.send(accepted).await.is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+        drop(accept_tx);
+
+        // Scrapes are rare and one-shot, so each metrics listener serves its
+        // own connections without going through the client accept loop.
+        for listener in metrics_listeners {
+            server_log!(db, Level::Info, "Metrics listening on {}", listener.local_addr()?);
+            let db = db.clone();
+            acceptors.push(tokio::spawn(async move {
+                while let Ok((socket, _)) = listener.accept().await {
+                    let db = db.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = metrics::serve(socket, &db).await {
+                            server_log!(db, Level::Debug, "metrics request failed: {}", e);
+                        }
+                    });
+                }
+            }));
+        }
+        // Fired on shutdown for connections parked in a command that would
+        // otherwise never look back at the server, such as WAIT.
+        let (closing_tx, _) = broadcast::channel(1);
+
+        let shutdown = async move {
+            match shutdown {
+                Some(mut rx) => {
+                    let _ = rx.recv().await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                Some(accept_result) = accept_rx.recv() => {
+                    match accept_result {
+                        Ok((socket, addr)) => {
+                            let ctx = ConnectionContext { id: db.next_client_id(), addr };
+                            server_log!(db, Level::Debug, "{} accepted", ctx);
+                            let db = db.clone();
+                            let closing = closing_tx.subscribe();
+                            tokio::spawn(async move {
+                                if let Err(e) = process_client(socket, db.clone(), ctx, closing).await {
+                                    server_log!(db, Level::Error, "{} error processing client: {}", ctx, e);
+                                }
+                                server_log!(db, Level::Debug, "{} closed", ctx);
+                            });
+                        }
+                        Err(e) => {
+                            server_log!(db, Level::Error, "Failed to accept connection: {}", e);
+                        }
+                    }
+                }
+                _ = &mut shutdown => {
+                    let _ = closing_tx.send(());
+                    server_log!(db, Level::Info, "Shutdown signal received, stopping server...");
+                    break;
+                }
+            }
+        }
+
+        for acceptor in acceptors {
+            acceptor.abort();
+        }
+        Ok(())
+    }
+}
+
+/// Runs a default-configured server on `port`.
+pub async fn run_server(port: u16, shutdown: Option<broadcast::Receiver<()>>) -> Result<(), Box<dyn std::error::Error>> {
+    Server::builder().port(port).build().await?.run(shutdown).await
+}
+
+/// Minimum free buffer space before each read.
+const READ_CHUNK: usize = 4096;
+/// Cap on how far ahead of the data the read buffer grows for a frame that
+/// announces a large length, so a bare header can't pin a huge allocation.
+const MAX_READ_RESERVE: usize = 1024 * 1024;
+/// Most input buffered while a client is blocked in WAIT. Past this the
+/// socket isn't read until the wait ends, so a client that keeps pipelining
+/// is held back by TCP instead of growing the buffer.
+const MAX_BLOCKED_INPUT: usize = 1024 * 1024;
+
+/// Bulk replies at least this large are written as header, payload and
+/// trailer slices instead of being copied into one encoded buffer.
+const STREAM_BULK_THRESHOLD: usize = 64 * 1024;
+
+/// Writes one reply and returns the number of bytes sent. `out` is the
+/// connection's reusable encode buffer, so replies of any size share one
+/// allocation instead of building a new `Vec` each time.
+async fn write_frame(socket: &mut TcpStream, out: &mut BytesMut, frame: &Frame) -> io::Result<usize> {
+    match frame {
+        Frame::Bulk(Some(data)) if data.len() >= STREAM_BULK_THRESHOLD => {
+            let header = format!("${}\r\n", data.len());
+            let mut slices = [
+                IoSlice::new(header.as_bytes()),
+                IoSlice::new(data),
+                IoSlice::new(b"\r\n"),
+            ];
+            let total = slices.iter().map(|slice| slice.len()).sum();
+            let mut slices = &mut slices[..];
+            while !slices.is_empty() {
+                let n = socket.write_vectored(slices).await?;
+                if n == 0 {
+                    return Err(io::ErrorKind::WriteZero.into());
+                }
+                IoSlice::advance_slices(&mut slices, n);
+            }
+            Ok(total)
+        }
+        _ => {
+            out.clear();
+            frame.encode_to(out);
+            socket.write_all(out).await?;
+            Ok(out.len())
+        }
+    }
+}
+
+/// Serves one client until it disconnects. `closing` fires when the server
+/// shuts down.
+async fn process_client(
+    mut socket: TcpStream,
+    db: Arc<Db>,
+    ctx: ConnectionContext,
+    mut closing: broadcast::Receiver<()>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut buffer = BytesMut::with_capacity(READ_CHUNK);
+    let mut out = BytesMut::with_capacity(4096);
+    let mut decoder = Decoder::new();
+    let _connected = db.stats().record_connection();
+    let mut client = Client::new(ctx.id);
+    client.authenticated = db.config().requirepass.is_none();
+
+    loop {
+        // A full BytesMut only grows by a few bytes per read, so make room
+        // for the rest of the frame being assembled up front.
+        let missing = decoder.bytes_needed().saturating_sub(buffer.len());
+        buffer.reserve(missing.clamp(READ_CHUNK, MAX_READ_RESERVE));
+        let n = socket.read_buf(&mut buffer).await?;
+        if n == 0 {
+            // The client closed its write side. Every complete frame it sent
+            // has been answered, and replies are written unbuffered, so
+            // nothing is left to flush.
+            return Ok(());
+        }
+        db.stats().record_input(n);
+        client.net_input += n as u64;
+
+        // A single read may carry several pipelined frames, so drain every
+        // complete frame before waiting on the socket again.
+        loop {
+            // Read per frame so CONFIG SET of the protocol limits applies at once.
+            let limits = db.config().limits();
+            match decoder.decode(&mut buffer, limits) {
+                // An empty or null multibulk is a no-op and gets no reply.
+                Ok(Some(Frame::Array(None))) => {}
+                Ok(Some(Frame::Array(Some(items)))) if items.is_empty() => {}
+                Ok(Some(frame)) => {
+                    match Command::from_frame(frame) {
+                        Ok(cmd) => {
+                            server_log!(db, Level::Trace, "{} command {}", ctx, cmd.name());
+                            // Wait out any CLIENT PAUSE that covers this command.
+                            loop {
+                                let lifted = db.pause().lifted();
+                                let Some(deadline) = cmd.paused_until(db.pause()) else {
+                                    break;
+                                };
+                                tokio::select! {
+                                    _ = tokio::time::sleep_until(deadline.into()) => {}
+                                    _ = lifted => {}
+                                }
+                            }
+                            if let Command::Wait { numreplicas, timeout } = cmd {
+                                // Nothing can ever satisfy a positive target.
+                                if numreplicas > 0 {
+                                    let wait = async {
+                                        match timeout {
+                                            Some(timeout) => tokio::time::sleep(timeout).await,
+                                            None => std::future::pending().await,
+                                        }
+                                    };
+                                    tokio::pin!(wait);
+                                    // Keep reading meanwhile so a client that hangs up
+                                    // is noticed; pipelined bytes stay buffered, up to
+                                    // MAX_BLOCKED_INPUT.
+                                    loop {
+                                        tokio::select! {
+                                            _ = &mut wait => break,
+                                            read = socket.read_buf(&mut buffer),
+                                                if buffer.len() < MAX_BLOCKED_INPUT => {
+                                                let n = read?;
+                                                if n == 0 {
+                                                    return Ok(());
+                                                }
+                                                db.stats().record_input(n);
+                                                client.net_input += n as u64;
+                                            }
+                                            _ = closing.recv() => return Ok(()),
+                                        }
+                                    }
+                                }
+                            }
+                            let quit = matches!(cmd, Command::Quit);
+                            let response = cmd.execute(&db, &mut client).unwrap_or_else(Frame::from);
+                            db.stats().record_command();
+                            let written = write_frame(&mut socket, &mut out, &response).await?;
+                            db.stats().record_output(written);
+                            client.net_output += written as u64;
+                            if quit {
+                                // Anything pipelined after QUIT is dropped.
+                                return Ok(());
+                            }
+                        }
+                        Err(e) => {
+                            let error = Frame::from(e);
+                            let written = write_frame(&mut socket, &mut out, &error).await?;
+                            db.stats().record_output(written);
+                            client.net_output += written as u64;
+                        }
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    // As in Redis, a protocol error ends the connection: there
+                    // is no telling where the next frame starts, so nothing
+                    // still buffered can be trusted. Other connections run in
+                    // their own tasks and are unaffected.
+                    server_log!(db, Level::Debug, "{} protocol error: {}", ctx, e);
+                    let error = Frame::Error(format!("ERR Protocol error: {}", e));
+                    let written = write_frame(&mut socket, &mut out, &error).await?;
+                    db.stats().record_output(written);
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis::Client;
+    use std::time::Duration;
+    use std::sync::Mutex;
+    use tokio::time::timeout;
+
+    const TEST_PORT: u16 = 6380; // Use a different port for testing
+
+    /// Binds `builder` and serves it in the background. Sending on the
+    /// returned channel shuts the server down.
+    async fn start(builder: ServerBuilder) -> (SocketAddr, broadcast::Sender<()>) {
+        let server = builder.build().await.unwrap();
+        let addr = server.local_addrs().unwrap()[0];
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        tokio::spawn(async move {
+            if let Err(e) = server.run(Some(shutdown_rx)).await {
+                eprintln!("Server error: {}", e);
+            }
+        });
+        (addr, shutdown_tx)
+    }
+
+    /// Sends `request` and returns whatever a single read brings back.
+    async fn exchange(stream: &mut TcpStream, request: &[u8]) -> Vec<u8> {
+        stream.write_all(request).await.unwrap();
+        let mut reply = vec![0u8; 1024];
+        let n = stream.read(&mut reply).await.unwrap();
+        reply.truncate(n);
+        reply
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_redis_integration() {
+        // Setup shutdown channel
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+
+        // Start server in a separate tokio runtime
+        let server_handle = tokio::spawn(async move {
+            if let Err(e) = run_server(TEST_PORT, Some(shutdown_rx)).await {
+                eprintln!("Server error: {}", e);
+            }
+        });
+
+        // Wait for server to start and try to connect
+        let mut client = None;
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            match Client::open(format!("redis://127.0.0.1:{}", TEST_PORT)) {
+                Ok(c) => {
+                    client = Some(c);
+                    break;
+                }
+                Err(_) => continue,
+            }
+        }
+
+        let client = client.expect("Failed to connect to test server");
+        let mut con = client.get_connection().unwrap();
+
+        // Run tests with timeout
+        let test_result = timeout(Duration::from_secs(5), async {
+            // Test SET and GET
+            let _: () = redis::cmd("SET")
+                .arg("test_key")
+                .arg("test_value")
+                .query(&mut con)
+                .unwrap();
+
+            let value: String = redis::cmd("GET")
+                .arg("test_key")
+                .query(&mut con)
+                .unwrap();
+
+            assert_eq!(value, "test_value");
+
+            // Test DEL
+            let deleted: i32 = redis::cmd("DEL")
+                .arg("test_key")
+                .query(&mut con)
+                .unwrap();
+
+            assert_eq!(deleted, 1);
+        }).await;
+
+        // Signal server to shut down
+        let _ = shutdown_tx.send(());
+
+        // Wait for server to shut down with timeout
+        let _ = timeout(Duration::from_secs(1), server_handle).await;
+
+        // Drop the connection explicitly
+        drop(con);
+        drop(client);
+
+        // Assert that the test completed within the timeout
+        assert!(test_result.is_ok(), "Test timed out");
+    }
+
+    #[tokio::test]
+    async fn test_empty_array_is_ignored() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"*0\r\n*-1\r\n*2\r\n$3\r\nGET\r\n$7\r\nmissing\r\n")
+            .await
+            .unwrap();
+
+        // The only reply on the wire should be the one for GET.
+        let mut reply = [0u8; 5];
+        timeout(Duration::from_secs(1), stream.read_exact(&mut reply))
+            .await
+            .expect("no reply to GET")
+            .unwrap();
+        assert_eq!(&reply, b"$-1\r\n");
+
+        let _ = shutdown_tx.send(());
+    }
+
+    /// Records every log line so tests can assert on what was emitted.
+    struct CaptureLogger;
+
+    static CAPTURED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    static LOGGER: CaptureLogger = CaptureLogger;
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            CAPTURED.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[tokio::test]
+    async fn test_loglevel_filters_command_logging() {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let db = Arc::new(Db::new());
+        db.set_config("loglevel", "debug").unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (socket, addr) = listener.accept().await.unwrap();
+        let ctx = ConnectionContext { id: 1, addr };
+        let (_closing_tx, closing) = broadcast::channel(1);
+        tokio::spawn({
+            let db = db.clone();
+            async move {
+                let _ = process_client(socket, db, ctx, closing).await;
+            }
+        });
+
+        let logged = |command: &str| {
+            let line = format!("{} command {}", ctx, command);
+            CAPTURED.lock().unwrap().contains(&line)
+        };
+
+        let mut reply = [0u8; 5];
+        client.write_all(b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n").await.unwrap();
+        client.read_exact(&mut reply).await.unwrap();
+        assert!(logged("GET"));
+
+        db.set_config("loglevel", "warning").unwrap();
+        client.write_all(b"*2\r\n$6\r\nEXISTS\r\n$1\r\nk\r\n").await.unwrap();
+        client.read_exact(&mut reply[..4]).await.unwrap();
+        assert!(!logged("EXISTS"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_server_builder() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0).maxmemory(1024 * 1024)).await;
+
+        let client = Client::open(format!("redis://{}", addr)).unwrap();
+        let mut con = client.get_connection().unwrap();
+        let _: () = redis::cmd("SET").arg("k").arg("v").query(&mut con).unwrap();
+        let value: String = redis::cmd("GET").arg("k").query(&mut con).unwrap();
+        assert_eq!(value, "v");
+
+        let maxmemory: Vec<String> = redis::cmd("CONFIG")
+            .arg("GET")
+            .arg("maxmemory")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(maxmemory, vec!["maxmemory", "1048576"]);
+
+        let _ = shutdown_tx.send(());
+    }
+
+    /// Borrows a free port from the OS and hands it back, for listeners
+    /// whose port has to be known up front.
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multiple_listeners_share_db() {
+        let ports = [free_port(), free_port()];
+        let (_, shutdown_tx) = start(Server::builder().ports(ports)).await;
+        let addrs = ports.map(|port| SocketAddr::from((Ipv4Addr::LOCALHOST, port)));
+
+        let mut first = Client::open(format!("redis://{}", addrs[0]))
+            .unwrap()
+            .get_connection()
+            .unwrap();
+        let mut second = Client::open(format!("redis://{}", addrs[1]))
+            .unwrap()
+            .get_connection()
+            .unwrap();
+
+        let _: () = redis::cmd("SET").arg("k").arg("v").query(&mut first).unwrap();
+        let value: String = redis::cmd("GET").arg("k").query(&mut second).unwrap();
+        assert_eq!(value, "v");
+
+        let _ = shutdown_tx.send(());
+
+        // Once the acceptors are gone, neither port takes new connections.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        for addr in addrs {
+            assert!(TcpStream::connect(addr).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quit_closes_connection() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"*1\r\n$4\r\nQUIT\r\n*2\r\n$3\r\nGET\r\n$1\r\nk\r\n")
+            .await
+            .unwrap();
+
+        let mut reply = Vec::new();
+        timeout(Duration::from_secs(1), stream.read_to_end(&mut reply))
+            .await
+            .expect("server did not close the connection")
+            .unwrap();
+        assert_eq!(reply, b"+OK\r\n");
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_error_prefixes() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut reply = vec![0u8; 64];
+
+        stream.write_all(b"*1\r\n$4\r\nNOPE\r\n").await.unwrap();
+        let n = stream.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b"-ERR unknown command 'NOPE', with args beginning with: \r\n");
+
+        stream.write_all(b"*garbage\r\n").await.unwrap();
+        let n = stream.read(&mut reply).await.unwrap();
+        assert!(reply[..n].starts_with(b"-ERR Protocol error: "));
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_large_value_round_trip() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let value: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let mut con = Client::open(format!("redis://{}", addr))
+            .unwrap()
+            .get_connection()
+            .unwrap();
+        let _: () = redis::cmd("SET").arg("big").arg(&value).query(&mut con).unwrap();
+        let fetched: Vec<u8> = redis::cmd("GET").arg("big").query(&mut con).unwrap();
+        assert!(fetched == value, "large value corrupted");
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_large_array_reply() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let keys: Vec<String> = (0..10_000).map(|i| format!("key:{}", i)).collect();
+        let mut con = Client::open(format!("redis://{}", addr))
+            .unwrap()
+            .get_connection()
+            .unwrap();
+        let reply: Vec<String> = redis::cmd("COMMAND")
+            .arg("GETKEYS")
+            .arg("EXISTS")
+            .arg(&keys)
+            .query(&mut con)
+            .unwrap();
+        assert!(reply == keys, "large array reply corrupted");
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_proto_max_bulk_len_is_live() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut reply = vec![0u8; 64];
+
+        stream
+            .write_all(b"*4\r\n$6\r\nCONFIG\r\n$3\r\nSET\r\n$18\r\nproto-max-bulk-len\r\n$3\r\n1mb\r\n")
+            .await
+            .unwrap();
+        let n = stream.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b"+OK\r\n");
+
+        let set = |len: usize| {
+            let mut request = format!("*3\r\n$3\r\nSET\r\n$1\r\nk\r\n${}\r\n", len).into_bytes();
+            request.extend(std::iter::repeat_n(b'x', len));
+            request.extend(b"\r\n");
+            request
+        };
+
+        stream.write_all(&set(1024 * 1024)).await.unwrap();
+        let n = stream.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b"+OK\r\n");
+
+        stream.write_all(&set(1024 * 1024 + 1)).await.unwrap();
+        let n = stream.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b"-ERR Protocol error: invalid bulk length\r\n");
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_net_byte_counters() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut reply = vec![0u8; 256];
+        let requests: [&[u8]; 2] = [
+            b"*1\r\n$4\r\nPING\r\n",
+            b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$5\r\nhello\r\n",
+        ];
+        let mut sent = 0;
+        let mut received = 0;
+        for request in requests {
+            stream.write_all(request).await.unwrap();
+            sent += request.len();
+            received += stream.read(&mut reply).await.unwrap();
+        }
+
+        let info_request = b"*2\r\n$4\r\nINFO\r\n$5\r\nstats\r\n";
+        stream.write_all(info_request).await.unwrap();
+        sent += info_request.len();
+        let n = stream.read(&mut reply).await.unwrap();
+        let info = String::from_utf8_lossy(&reply[..n]);
+
+        // The INFO request itself has been read, but its reply not yet sent.
+        assert!(info.contains(&format!("total_net_input_bytes:{}\r\n", sent)), "{}", info);
+        assert!(info.contains(&format!("total_net_output_bytes:{}\r", received)), "{}", info);
+        received += n;
+
+        // Another connection's traffic shows in the totals but not in this
+        // connection's own counters.
+        let mut other = TcpStream::connect(addr).await.unwrap();
+        let value = "v".repeat(1000);
+        let request = format!("*3\r\n$3\r\nSET\r\n$1\r\nk\r\n${}\r\n{}\r\n", value.len(), value);
+        assert_eq!(exchange(&mut other, request.as_bytes()).await, b"+OK\r\n");
+        let client_info = b"*2\r\n$6\r\nCLIENT\r\n$4\r\nINFO\r\n";
+        sent += client_info.len();
+        let reply = exchange(&mut stream, client_info).await;
+        let reply = String::from_utf8_lossy(&reply);
+        let expected = format!(" tot-net-in={} tot-net-out={}\n", sent, received);
+        assert!(reply.ends_with(&format!("{}\r\n", expected)), "{}", reply);
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_frame_larger_than_read_buffer() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let value = vec![b'x'; 3 * READ_CHUNK + 17];
+        let mut request = format!("*3\r\n$3\r\nSET\r\n$1\r\nk\r\n${}\r\n", value.len()).into_bytes();
+        request.extend_from_slice(&value);
+        request.extend_from_slice(b"\r\n*2\r\n$3\r\nGET\r\n$1\r\nk\r\n");
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(&request).await.unwrap();
+
+        let mut expected = b"+OK\r\n".to_vec();
+        expected.extend_from_slice(&Frame::Bulk(Some(value.into())).encode());
+        let mut reply = vec![0u8; expected.len()];
+        timeout(Duration::from_secs(2), stream.read_exact(&mut reply))
+            .await
+            .expect("server stalled on a large frame")
+            .unwrap();
+        assert!(reply == expected, "large frame corrupted");
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_client_ids_are_distinct() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(b"*2\r\n$6\r\nCLIENT\r\n$2\r\nID\r\n").await.unwrap();
+            let mut reply = vec![0u8; 32];
+            let n = stream.read(&mut reply).await.unwrap();
+            let mut bytes = BytesMut::from(&reply[..n]);
+            match Frame::parse(&mut bytes).unwrap() {
+                Some(Frame::Integer(id)) => ids.push(id),
+                other => panic!("expected an integer, got {:?}", other),
+            }
+        }
+        assert_ne!(ids[0], ids[1]);
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_half_closed_client_gets_all_replies() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n*2\r\n$3\r\nGET\r\n$1\r\nk\r\n*1\r\n$4\r\nPING\r\n")
+            .await
+            .unwrap();
+        stream.shutdown().await.unwrap();
+
+        let mut reply = Vec::new();
+        timeout(Duration::from_secs(1), stream.read_to_end(&mut reply))
+            .await
+            .expect("server did not close the connection")
+            .unwrap();
+        assert_eq!(reply, b"+OK\r\n$1\r\nv\r\n+PONG\r\n");
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_custom_tcp_backlog() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0).tcp_backlog(4)).await;
+
+        // More simultaneous connections than the backlog holds; the accept
+        // loop drains the queue, so every one of them is still served.
+        let mut streams = Vec::new();
+        for _ in 0..16 {
+            streams.push(TcpStream::connect(addr).await.unwrap());
+        }
+        for stream in &mut streams {
+            stream
+                .write_all(b"*3\r\n$6\r\nCONFIG\r\n$3\r\nGET\r\n$11\r\ntcp-backlog\r\n")
+                .await
+                .unwrap();
+        }
+        for stream in &mut streams {
+            let mut reply = vec![0u8; 64];
+            let n = timeout(Duration::from_secs(1), stream.read(&mut reply))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(&reply[..n], b"*2\r\n$11\r\ntcp-backlog\r\n$1\r\n4\r\n");
+        }
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_max_command_args() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut reply = vec![0u8; 64];
+
+        stream
+            .write_all(b"*4\r\n$6\r\nCONFIG\r\n$3\r\nSET\r\n$16\r\nmax-command-args\r\n$4\r\n1024\r\n")
+            .await
+            .unwrap();
+        let n = stream.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b"+OK\r\n");
+
+        let exists = |argc: usize| {
+            let mut request = format!("*{}\r\n$6\r\nEXISTS\r\n", argc).into_bytes();
+            for _ in 1..argc {
+                request.extend(b"$1\r\nk\r\n");
+            }
+            request
+        };
+
+        stream.write_all(&exists(1024)).await.unwrap();
+        let n = stream.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b":0\r\n");
+
+        // Only the header is sent: the count alone is enough to refuse it.
+        stream.write_all(b"*1025\r\n").await.unwrap();
+        let n = stream.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b"-ERR Protocol error: invalid multibulk length\r\n");
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_integer_replies_are_typed() {
+        let builder = Server::builder()
+            .port(0)
+            .maxmemory_policy(MaxMemoryPolicy::AllKeysLfu)
+            .enable_debug_command(true);
+        let (addr, shutdown_tx) = start(builder).await;
+
+        let client = Client::open(format!("redis://{}", addr)).unwrap();
+        let mut con = client.get_connection().unwrap();
+        let _: () = redis::cmd("SET").arg("n").arg("10").query(&mut con).unwrap();
+        let _: () = redis::cmd("SET").arg("s").arg("v").query(&mut con).unwrap();
+
+        // Typed as redis::Value so a bulk "1" can't pass for an integer.
+        let commands: &[&[&str]] = &[
+            &["EXISTS", "n", "s", "missing"],
+            &["INCR", "n"],
+            &["DECR", "n"],
+            &["INCRBY", "n", "5"],
+            &["DECRBY", "n", "5"],
+            &["COPY", "s", "t"],
+            &["DEL", "t"],
+            &["DEL", "missing"],
+            &["DBSIZE"],
+            &["CLIENT", "ID"],
+            &["OBJECT", "FREQ", "s"],
+            &["DEBUG", "STRINGMATCH-LEN", "*", "s"],
+        ];
+        for args in commands {
+            let mut cmd = redis::cmd(args[0]);
+            for arg in &args[1..] {
+                cmd.arg(*arg);
+            }
+            let reply: redis::Value = cmd.query(&mut con).unwrap();
+            assert!(matches!(reply, redis::Value::Int(_)), "{:?} replied {:?}", args, reply);
+        }
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_cli_handshake() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let stream = &mut stream;
+
+        // A load balancer's inline probe, before any RESP.
+        assert_eq!(exchange(stream, b"PING\r\n").await, b"+PONG\r\n");
+        // redis-cli asks for command docs on connect, and a RESP3 client
+        // tries HELLO; both must leave the connection usable.
+        assert_eq!(exchange(stream, b"*2\r\n$7\r\nCOMMAND\r\n$4\r\nDOCS\r\n").await, b"*0\r\n");
+        let hello = exchange(stream, b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n").await;
+        assert!(hello.starts_with(b"-ERR unknown command 'HELLO'"));
+        assert_eq!(exchange(stream, b"*1\r\n$4\r\nPING\r\n").await, b"+PONG\r\n");
+        assert_eq!(exchange(stream, b"set k v\n").await, b"+OK\r\n");
+        assert_eq!(exchange(stream, b"GET k\r\n").await, b"$1\r\nv\r\n");
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_client_pause() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut admin = TcpStream::connect(addr).await.unwrap();
+        let mut other = TcpStream::connect(addr).await.unwrap();
+        let set = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n";
+
+        // WRITE mode: reads go straight through, writes wait for the deadline.
+        let pause = b"*4\r\n$6\r\nCLIENT\r\n$5\r\nPAUSE\r\n$3\r\n300\r\n$5\r\nWRITE\r\n";
+        assert_eq!(exchange(&mut admin, pause).await, b"+OK\r\n");
+        let start = std::time::Instant::now();
+        assert_eq!(exchange(&mut other, b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n").await, b"$-1\r\n");
+        assert!(start.elapsed() < Duration::from_millis(200));
+        assert_eq!(exchange(&mut other, set).await, b"+OK\r\n");
+        assert!(start.elapsed() >= Duration::from_millis(250));
+
+        // ALL mode holds reads too, until CLIENT UNPAUSE lifts it early.
+        let pause = b"*3\r\n$6\r\nCLIENT\r\n$5\r\nPAUSE\r\n$5\r\n10000\r\n";
+        assert_eq!(exchange(&mut admin, pause).await, b"+OK\r\n");
+        let start = std::time::Instant::now();
+        other.write_all(b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n").await.unwrap();
+        let mut reply = vec![0u8; 64];
+        assert!(timeout(Duration::from_millis(200), other.read(&mut reply)).await.is_err());
+        let unpause = b"*2\r\n$6\r\nCLIENT\r\n$7\r\nUNPAUSE\r\n";
+        assert_eq!(exchange(&mut admin, unpause).await, b"+OK\r\n");
+        let n = timeout(Duration::from_secs(1), other.read(&mut reply)).await.unwrap().unwrap();
+        assert_eq!(&reply[..n], b"$1\r\nv\r\n");
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_protocol_error_closes_only_that_connection() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut bad = TcpStream::connect(addr).await.unwrap();
+        let mut good = TcpStream::connect(addr).await.unwrap();
+        let mut reply = vec![0u8; 128];
+
+        // The valid command pipelined after the garbage is never run.
+        bad.write_all(b"*1\r\n$x\r\n*1\r\n$4\r\nPING\r\n").await.unwrap();
+        let mut received = Vec::new();
+        loop {
+            let n = timeout(Duration::from_secs(1), bad.read(&mut reply)).await.unwrap().unwrap();
+            if n == 0 {
+                break;
+            }
+            received.extend_from_slice(&reply[..n]);
+        }
+        assert_eq!(received, b"-ERR Protocol error: invalid frame format\r\n");
+
+        good.write_all(b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n").await.unwrap();
+        let n = good.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b"+OK\r\n");
+        good.write_all(b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n").await.unwrap();
+        let n = good.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b"$1\r\nv\r\n");
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_wait_timeouts() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut reply = vec![0u8; 64];
+        let wait = |numreplicas: &str, timeout: &str| {
+            format!(
+                "*3\r\n$4\r\nWAIT\r\n${}\r\n{}\r\n${}\r\n{}\r\n",
+                numreplicas.len(),
+                numreplicas,
+                timeout.len(),
+                timeout
+            )
+            .into_bytes()
+        };
+
+        let start = std::time::Instant::now();
+        stream.write_all(&wait("0", "0")).await.unwrap();
+        let n = stream.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b":0\r\n");
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        let start = std::time::Instant::now();
+        stream.write_all(&wait("1", "200")).await.unwrap();
+        let n = stream.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b":0\r\n");
+        assert!(start.elapsed() >= Duration::from_millis(200));
+
+        // A zero timeout with a target that can't be met blocks for good.
+        let mut blocked = TcpStream::connect(addr).await.unwrap();
+        blocked.write_all(&wait("1", "0")).await.unwrap();
+        assert!(timeout(Duration::from_millis(300), blocked.read(&mut reply)).await.is_err());
+
+        stream.write_all(&wait("1", "-1")).await.unwrap();
+        let n = stream.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b"-ERR timeout is negative\r\n");
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_total_commands_processed() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        async fn commands_processed(stream: &mut TcpStream) -> u64 {
+            stream.write_all(b"*2\r\n$4\r\nINFO\r\n$5\r\nstats\r\n").await.unwrap();
+            let mut reply = vec![0u8; 512];
+            let n = stream.read(&mut reply).await.unwrap();
+            let mut bytes = BytesMut::from(&reply[..n]);
+            let Some(Frame::Bulk(Some(info))) = Frame::parse(&mut bytes).unwrap() else {
+                panic!("expected INFO text");
+            };
+            String::from_utf8(info.to_vec())
+                .unwrap()
+                .lines()
+                .find_map(|line| line.strip_prefix("total_commands_processed:"))
+                .unwrap()
+                .parse()
+                .unwrap()
+        }
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let before = commands_processed(&mut stream).await;
+
+        // Four dispatched commands, one of them failing; the unknown command
+        // is never dispatched and isn't counted.
+        stream
+            .write_all(
+                b"*1\r\n$4\r\nPING\r\n*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n\
+                  *2\r\n$4\r\nINCR\r\n$1\r\nk\r\n*2\r\n$3\r\nGET\r\n$1\r\nk\r\n\
+                  *1\r\n$4\r\nNOPE\r\n",
+            )
+            .await
+            .unwrap();
+        let mut reply = vec![0u8; 512];
+        let mut received = 0;
+        while received < 5 {
+            let n = stream.read(&mut reply).await.unwrap();
+            received += reply[..n].iter().filter(|&&b| b == b'\n').count();
+        }
+
+        // The first INFO is counted once it has run.
+        assert_eq!(commands_processed(&mut stream).await, before + 1 + 4);
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let port = free_port();
+        let (addr, shutdown_tx) = start(Server::builder().port(0).metrics_port(port)).await;
+        let metrics_addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+        let mut reply = vec![0u8; 16];
+        let n = stream.read(&mut reply).await.unwrap();
+        assert_eq!(&reply[..n], b"+PONG\r\n");
+
+        async fn get(addr: SocketAddr, path: &str) -> String {
+            let mut http = TcpStream::connect(addr).await.unwrap();
+            let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            http.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            timeout(Duration::from_secs(1), http.read_to_string(&mut response))
+                .await
+                .unwrap()
+                .unwrap();
+            response
+        }
+
+        let response = get(metrics_addr, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains("\nredis_commands_processed_total 1\n"), "{}", response);
+        assert!(response.contains("\nredis_connected_clients 1\n"), "{}", response);
+
+        let response = get(metrics_addr, "/").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", response);
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_null_bulk_argument_is_protocol_error() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0)).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$-1\r\n")
+            .await
+            .unwrap();
+        let mut received = Vec::new();
+        let mut reply = vec![0u8; 64];
+        loop {
+            let n = timeout(Duration::from_secs(1), stream.read(&mut reply))
+                .await
+                .unwrap()
+                .unwrap();
+            if n == 0 {
+                break;
+            }
+            received.extend_from_slice(&reply[..n]);
+        }
+        assert_eq!(received, b"-ERR Protocol error: invalid bulk length\r\n");
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_blocked_wait_ends_on_disconnect_and_shutdown() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (closing_tx, _) = broadcast::channel(1);
+
+        let connect = || {
+            let listener = &listener;
+            let closing = closing_tx.subscribe();
+            async move {
+                let client = TcpStream::connect(addr).await.unwrap();
+                let (socket, peer) = listener.accept().await.unwrap();
+                let ctx = ConnectionContext { id: 1, addr: peer };
+                let db = Arc::new(Db::new());
+                let task = tokio::spawn(async move {
+                    process_client(socket, db, ctx, closing).await.is_ok()
+                });
+                (client, task)
+            }
+        };
+        let wait = b"*3\r\n$4\r\nWAIT\r\n$1\r\n1\r\n$1\r\n0\r\n";
+
+        // The client gives up on a WAIT that can never finish.
+        let (mut client, task) = connect().await;
+        client.write_all(wait).await.unwrap();
+        drop(client);
+        assert!(timeout(Duration::from_secs(1), task).await.unwrap().unwrap());
+
+        // The server shuts down under one.
+        let (mut client, task) = connect().await;
+        client.write_all(wait).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        closing_tx.send(()).unwrap();
+        assert!(timeout(Duration::from_secs(1), task).await.unwrap().unwrap());
+
+        // A client that keeps pipelining under one is no longer read once
+        // MAX_BLOCKED_INPUT is buffered, so its writes stall.
+        let (mut client, task) = connect().await;
+        client.write_all(wait).await.unwrap();
+        let flood = b"*1\r\n$4\r\nPING\r\n".repeat(32 * 1024 * 1024 / 14);
+        assert!(timeout(Duration::from_millis(500), client.write_all(&flood)).await.is_err());
+        closing_tx.send(()).unwrap();
+        assert!(timeout(Duration::from_secs(1), task).await.unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_password() {
+        let (addr, shutdown_tx) = start(Server::builder().port(0).password("secret")).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        assert_eq!(
+            exchange(&mut stream, b"*1\r\n$4\r\nPING\r\n").await,
+            b"-NOAUTH Authentication required.\r\n"
+        );
+        assert_eq!(
+            exchange(&mut stream, b"*2\r\n$4\r\nAUTH\r\n$5\r\nwrong\r\n").await,
+            b"-WRONGPASS invalid username-password pair or user is disabled.\r\n"
+        );
+        assert_eq!(
+            exchange(&mut stream, b"*2\r\n$4\r\nAUTH\r\n$6\r\nsecret\r\n").await,
+            b"+OK\r\n"
+        );
+        assert_eq!(exchange(&mut stream, b"*1\r\n$4\r\nPING\r\n").await, b"+PONG\r\n");
+
+        // Turning the password off lets new connections straight in.
+        let request = b"*4\r\n$6\r\nCONFIG\r\n$3\r\nSET\r\n$11\r\nrequirepass\r\n$0\r\n\r\n";
+        assert_eq!(exchange(&mut stream, request).await, b"+OK\r\n");
+        let mut other = TcpStream::connect(addr).await.unwrap();
+        assert_eq!(exchange(&mut other, b"*1\r\n$4\r\nPING\r\n").await, b"+PONG\r\n");
+
+        let _ = shutdown_tx.send(());
+    }
+} 
\ No newline at end of file
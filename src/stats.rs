@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Server-wide counters reported in the `stats` section of INFO.
+#[derive(Debug, Default)]
+pub struct Stats {
+    net_input_bytes: AtomicU64,
+    net_output_bytes: AtomicU64,
+    evicted_keys: AtomicU64,
+    commands_processed: AtomicU64,
+    connected_clients: AtomicU64,
+}
+
+/// Holds one slot in the connected-clients count, given back on drop so
+/// every way out of a connection is counted.
+pub struct ConnectedClient<'a>(&'a Stats);
+
+impl Drop for ConnectedClient<'_> {
+    fn drop(&mut self) {
+        self.0.connected_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Stats {
+    /// Counts bytes read from a client socket, before any parsing.
+    pub fn record_input(&self, bytes: usize) {
+        self.net_input_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Counts bytes written back to a client socket.
+    pub fn record_output(&self, bytes: usize) {
+        self.net_output_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Counts a key removed to get back under maxmemory.
+    pub fn record_eviction(&self) {
+        self.evicted_keys.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a command that was dispatched, whatever its reply.
+    pub fn record_command(&self) {
+        self.commands_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a client connection until the returned guard is dropped.
+    pub fn record_connection(&self) -> ConnectedClient<'_> {
+        self.connected_clients.fetch_add(1, Ordering::Relaxed);
+        ConnectedClient(self)
+    }
+
+    pub fn net_input_bytes(&self) -> u64 {
+        self.net_input_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn net_output_bytes(&self) -> u64 {
+        self.net_output_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn evicted_keys(&self) -> u64 {
+        self.evicted_keys.load(Ordering::Relaxed)
+    }
+
+    pub fn commands_processed(&self) -> u64 {
+        self.commands_processed.load(Ordering::Relaxed)
+    }
+
+    pub fn connected_clients(&self) -> u64 {
+        self.connected_clients.load(Ordering::Relaxed)
+    }
+}